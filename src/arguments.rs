@@ -0,0 +1,292 @@
+//! Rewriting of compiler arguments so that the compiler only reports dependencies.
+
+use std::path::Path;
+
+use crate::compiler::CompilerFamily;
+
+/// Compiler launchers that take the compiler command as their arguments.
+pub const KNOWN_LAUNCHERS: &[&str] = &["ccache", "sccache", "distcc", "icecc", "buildcache"];
+
+/// How an option takes its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    /// `-MD`
+    None,
+    /// `--output file`
+    Separate,
+    /// `--output=file`, `/Fofile`
+    Joined,
+    /// `-MF file` or `-MFfile`
+    JoinedOrSeparate,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Spec {
+    name: &'static str,
+    value: Value,
+}
+
+const fn spec(name: &'static str, value: Value) -> Spec {
+    Spec { name, value }
+}
+
+/// Flags that generate dependency information.
+///
+/// They make the compiler write depfiles into the build tree as a side effect, or conflict with
+/// the flags this tool inserts.
+const DEPENDENCY_FLAGS: &[Spec] = &[
+    spec("-M", Value::None),
+    spec("-MM", Value::None),
+    spec("-MD", Value::None),
+    spec("-MMD", Value::None),
+    spec("-MG", Value::None),
+    spec("-MP", Value::None),
+    spec("-MV", Value::None),
+    spec("-MF", Value::JoinedOrSeparate),
+    spec("-MT", Value::JoinedOrSeparate),
+    spec("-MQ", Value::JoinedOrSeparate),
+    spec("-MJ", Value::JoinedOrSeparate),
+    spec("--dependencies", Value::None),
+    spec("--user-dependencies", Value::None),
+    spec("--write-dependencies", Value::None),
+    spec("--write-user-dependencies", Value::None),
+    spec("--print-missing-file-dependencies", Value::None),
+    spec("-save-temps", Value::None),
+    spec("--save-temps", Value::None),
+    spec("-save-temps=", Value::Joined),
+    spec("-fdeps-format=", Value::Joined),
+    spec("-fdeps-file=", Value::Joined),
+    spec("-fdeps-target=", Value::Joined),
+];
+
+/// Flags of cl that generate dependency information or preprocessed files.
+const MSVC_DEPENDENCY_FLAGS: &[Spec] = &[
+    spec("/showIncludes", Value::None),
+    spec("-showIncludes", Value::None),
+    spec("/showIncludes:user", Value::None),
+    spec("-showIncludes:user", Value::None),
+    spec("/sourceDependencies:directives", Value::Separate),
+    spec("-sourceDependencies:directives", Value::Separate),
+    spec("/sourceDependencies", Value::Separate),
+    spec("-sourceDependencies", Value::Separate),
+    spec("/scanDependencies", Value::Separate),
+    spec("-scanDependencies", Value::Separate),
+    spec("/P", Value::None),
+    spec("-P", Value::None),
+    spec("/E", Value::None),
+    spec("-E", Value::None),
+    spec("/EP", Value::None),
+    spec("-EP", Value::None),
+    spec("/Fi", Value::Joined),
+    spec("-Fi", Value::Joined),
+];
+
+/// Flags of gcc and clang that name the output file.
+const OUTPUT_FLAGS: &[Spec] = &[
+    spec("-o", Value::JoinedOrSeparate),
+    spec("--output", Value::Separate),
+    spec("--output=", Value::Joined),
+];
+
+/// Flags of cl that name the output file. Checked in order, so `/Fo:` must precede `/Fo`.
+const MSVC_OUTPUT_FLAGS: &[Spec] = &[
+    spec("/Fo:", Value::JoinedOrSeparate),
+    spec("-Fo:", Value::JoinedOrSeparate),
+    spec("/Fo", Value::Joined),
+    spec("-Fo", Value::Joined),
+    spec("/Fe:", Value::JoinedOrSeparate),
+    spec("-Fe:", Value::JoinedOrSeparate),
+    spec("/Fe", Value::Joined),
+    spec("-Fe", Value::Joined),
+];
+
+/// Flags that name the depfile.
+const DEPFILE_FLAGS: &[Spec] = &[spec("-MF", Value::JoinedOrSeparate)];
+
+/// Flags that start with an output flag but are not.
+const NOT_OUTPUT_FLAGS: &[&str] = &["-objc", "-object"];
+
+/// Returns how many arguments from `args[i]` match `spec`, if matches.
+fn matches(spec: &Spec, args: &[String], i: usize) -> Option<usize> {
+    let arg = args[i].as_str();
+    let separate = if i + 1 < args.len() { 2 } else { 1 };
+    match spec.value {
+        Value::None if arg == spec.name => Some(1),
+        Value::Separate if arg == spec.name => Some(separate),
+        Value::Joined if arg.starts_with(spec.name) => Some(1),
+        Value::JoinedOrSeparate if arg == spec.name => Some(separate),
+        Value::JoinedOrSeparate if arg.starts_with(spec.name) => Some(1),
+        _ => None,
+    }
+}
+
+/// Returns the value of the last option of `specs`.
+fn last_value<F>(args: &[String], specs: &[Spec], keep: F) -> Option<String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut result = None;
+    let mut i = 1;
+    while i < args.len() {
+        let matched = if keep(&args[i]) {
+            None
+        } else {
+            specs
+                .iter()
+                .find_map(|v| matches(v, args, i).map(|n| (v, n)))
+        };
+        match matched {
+            Some((spec, n)) => {
+                result = if n == 2 {
+                    Some(args[i + 1].clone())
+                } else {
+                    let value = &args[i][spec.name.len()..];
+                    let value = value.strip_prefix('=').unwrap_or(value);
+                    (!value.is_empty()).then(|| String::from(value))
+                };
+                i += n;
+            }
+            None => i += 1,
+        }
+    }
+    result
+}
+
+/// Removes options of `specs` with their values. `args[0]` is kept as is.
+fn remove_options<F>(args: &[String], specs: &[Spec], keep: F) -> Vec<String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut result = Vec::with_capacity(args.len());
+    result.extend(args.first().cloned());
+    let mut i = 1;
+    while i < args.len() {
+        let matched = if keep(&args[i]) {
+            None
+        } else {
+            specs.iter().find_map(|v| matches(v, args, i))
+        };
+        match matched {
+            Some(n) => i += n,
+            None => {
+                result.push(args[i].clone());
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Removes dependency generation flags in a `-Wp,` list. Returns `None` if nothing remains.
+fn rewrite_preprocessor_flags(arg: &str) -> Option<String> {
+    let mut flags = arg.split(',').skip(1);
+    let mut result = Vec::new();
+    while let Some(flag) = flags.next() {
+        match flag {
+            // `-Wp,-MD,file` takes the depfile as the next item
+            "-MD" | "-MMD" | "-MF" | "-MT" | "-MQ" => {
+                flags.next();
+            }
+            "-M" | "-MM" | "-MG" | "-MP" => {}
+            flag => result.push(flag),
+        }
+    }
+    if result.is_empty() {
+        None
+    } else {
+        Some(format!("-Wp,{}", result.join(",")))
+    }
+}
+
+/// Removes flags that generate dependency information (`-MD`, `-MMD`, `-MF`, `-MT`, `-MQ`, ...)
+/// including those passed through `-Wp,`.
+///
+/// `args[0]` is regarded as the compiler and kept as is.
+pub fn remove_dependency_flags(args: &[String]) -> Vec<String> {
+    let args = remove_options(args, DEPENDENCY_FLAGS, |_| false);
+    let mut result = Vec::with_capacity(args.len());
+    for (i, arg) in args.into_iter().enumerate() {
+        if i > 0 && arg.starts_with("-Wp,") {
+            result.extend(rewrite_preprocessor_flags(&arg));
+        } else {
+            result.push(arg);
+        }
+    }
+    result
+}
+
+/// Removes flags of cl that generate dependency information (`/showIncludes`,
+/// `/sourceDependencies`, `/scanDependencies`) or preprocessed files (`/P`, `/E`, `/EP`, `/Fi`).
+///
+/// `args[0]` is regarded as the compiler and kept as is.
+pub fn remove_msvc_dependency_flags(args: &[String]) -> Vec<String> {
+    remove_options(args, MSVC_DEPENDENCY_FLAGS, |_| false)
+}
+
+/// Removes every output flag: `-o file`, `-ofile`, `-o=file`, `--output file` and
+/// `--output=file` including repeated ones.
+///
+/// A trailing `-o` without value is removed alone. `args[0]` is kept as is.
+pub fn remove_output_flags(args: &[String]) -> Vec<String> {
+    remove_options(args, OUTPUT_FLAGS, |arg| {
+        NOT_OUTPUT_FLAGS.iter().any(|v| arg.starts_with(v))
+    })
+}
+
+/// Removes every output flag of cl: `/Fofile`, `/Fo:file`, `/Fo: file` and `/Fefile` including
+/// repeated ones.
+///
+/// `args[0]` is kept as is.
+pub fn remove_msvc_output_flags(args: &[String]) -> Vec<String> {
+    remove_options(args, MSVC_OUTPUT_FLAGS, |_| false)
+}
+
+/// Returns the output file named by the last output flag of the compiler family of `args[0]`.
+pub fn output_argument(args: &[String]) -> Option<String> {
+    match CompilerFamily::detect(args) {
+        CompilerFamily::Gcc => last_value(args, OUTPUT_FLAGS, |arg| {
+            NOT_OUTPUT_FLAGS.iter().any(|v| arg.starts_with(v))
+        }),
+        CompilerFamily::Msvc => last_value(args, MSVC_OUTPUT_FLAGS, |_| false),
+    }
+}
+
+/// Returns the depfile named by the last `-MF`, `-Wp,-MD,file` or `-Wp,-MMD,file`.
+pub fn depfile_argument(args: &[String]) -> Option<String> {
+    let mut result = last_value(args, DEPFILE_FLAGS, |_| false);
+    for arg in args.iter().skip(1) {
+        let mut flags = match arg.strip_prefix("-Wp,") {
+            Some(flags) => flags.split(','),
+            None => continue,
+        };
+        while let Some(flag) = flags.next() {
+            if matches!(flag, "-MD" | "-MMD" | "-MF") {
+                if let Some(file) = flags.next() {
+                    result = Some(String::from(file));
+                }
+            }
+        }
+    }
+    result
+}
+
+fn is_launcher(arg: &str, launchers: &[String]) -> bool {
+    let name = Path::new(arg)
+        .file_stem()
+        .and_then(|v| v.to_str())
+        .unwrap_or(arg);
+    KNOWN_LAUNCHERS.contains(&name) || launchers.iter().any(|v| v == arg || v.as_str() == name)
+}
+
+/// Removes compiler launchers such as `ccache` at the head of `args`, so that `args[0]` is the
+/// real compiler.
+///
+/// `launchers` are matched by name or path in addition to [`KNOWN_LAUNCHERS`].
+pub fn remove_launchers(args: &[String], launchers: &[String]) -> Vec<String> {
+    let skip = args
+        .iter()
+        .take(args.len().saturating_sub(1))
+        .take_while(|v| is_launcher(v, launchers))
+        .count();
+    args[skip..].to_vec()
+}
@@ -0,0 +1,102 @@
+//! Depfiles left in the build tree by `-MD`, read without running the compiler.
+use log::{trace, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::arguments::{depfile_argument, output_argument, remove_launchers};
+use crate::compile_command::CompileCommand;
+use crate::dependency::resolve_dependencies;
+use crate::depfile::parse_depfile;
+use crate::error::{Error, Result};
+
+/// Dependencies read from a depfile in the build tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildDepfile {
+    pub path: PathBuf,
+    /// Canonicalized dependencies.
+    pub dependencies: Vec<PathBuf>,
+    /// Whether any dependency is newer than the depfile, i.e. the build is out of date.
+    pub stale: bool,
+}
+
+/// Expands `{output}`, `{file}` and `{stem}` in `pattern`. `None` if a placeholder is unknown.
+fn expand_pattern(pattern: &str, output: Option<&str>, file: &Path) -> Option<String> {
+    let mut result = String::from(pattern);
+    if result.contains("{output}") {
+        result = result.replace("{output}", output?);
+    }
+    if result.contains("{stem}") {
+        result = result.replace("{stem}", file.file_stem()?.to_str()?);
+    }
+    Some(result.replace("{file}", file.to_str()?))
+}
+
+/// Returns paths where the build may have written the depfile of `command`, most likely first.
+///
+/// These are the value of `-MF`, `pattern` and `<output>.d` and `<output stem>.d`. `pattern` may
+/// contain `{output}`, `{file}` and `{stem}`, e.g. `deps/{stem}.d`.
+pub fn depfile_candidates(command: &CompileCommand, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
+    let args = remove_launchers(&command.arguments()?, &[]);
+    let output = command
+        .output
+        .as_ref()
+        .and_then(|v| v.to_str().map(String::from))
+        .or_else(|| output_argument(&args));
+
+    let mut result = Vec::new();
+    result.extend(depfile_argument(&args).map(PathBuf::from));
+    if let Some(pattern) = pattern {
+        result.extend(expand_pattern(pattern, output.as_deref(), &command.file).map(PathBuf::from));
+    }
+    if let Some(output) = output {
+        // `foo.o.d` of CMake and Ninja, and `foo.d` of `-MD`
+        result.push(PathBuf::from(format!("{}.d", output)));
+        result.push(PathBuf::from(output).with_extension("d"));
+    }
+    let mut candidates = Vec::new();
+    for path in result {
+        let path = command.directory.join(path);
+        if !candidates.contains(&path) {
+            candidates.push(path);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Finds the depfile of `command` in the build tree and reads dependencies from it.
+///
+/// See [`depfile_candidates`] for where depfiles are searched.
+pub fn read_build_depfile(command: &CompileCommand, pattern: Option<&str>) -> Result<BuildDepfile> {
+    let candidates = depfile_candidates(command, pattern)?;
+    trace!("read_build_depfile: candidates={:?}", candidates);
+    let path = candidates
+        .into_iter()
+        .find(|v| v.is_file())
+        .ok_or(Error::DepfileNotFoundError)?;
+    let content = fs::read_to_string(&path)?;
+    let depfile = parse_depfile(&content)?;
+    let dependencies = resolve_dependencies(depfile.prerequisites(), &command.directory)?;
+
+    let modified = fs::metadata(&path)?.modified()?;
+    let newer: Vec<_> = dependencies
+        .iter()
+        .filter(|v| {
+            fs::metadata(v)
+                .and_then(|v| v.modified())
+                .map(|v| v > modified)
+                .unwrap_or(false)
+        })
+        .collect();
+    if !newer.is_empty() {
+        warn!(
+            "Stale depfile {:?}: older than {:?}",
+            path,
+            newer.iter().take(3).collect::<Vec<_>>()
+        );
+    }
+    Ok(BuildDepfile {
+        stale: !newer.is_empty(),
+        path,
+        dependencies,
+    })
+}
@@ -0,0 +1,159 @@
+//! Compile commands recovered from build logs such as `make -nkw` and `ninja -v`, for projects
+//! without `compile_commands.json`.
+use log::trace;
+use std::path::{Path, PathBuf};
+
+use crate::arguments::{output_argument, remove_launchers};
+use crate::compile_command::CompileCommand;
+
+/// Extensions of sources compiled into translation units.
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cp", "cpp", "cxx", "c++", "C", "CC", "CPP", "m", "mm", "M", "cu", "cppm", "ccm",
+    "cxxm", "c++m", "ixx",
+];
+
+/// Compiler names without cross-compilation prefixes such as `arm-none-eabi-` and version suffixes
+/// such as `-12`.
+const COMPILER_NAMES: &[&str] = &[
+    "cc", "c++", "gcc", "g++", "clang", "clang++", "clang-cl", "cl", "icc", "icpc", "icx", "icpx",
+];
+
+fn is_compiler(arg: &str) -> bool {
+    let name = match Path::new(arg).file_name().and_then(|v| v.to_str()) {
+        Some(name) => name.to_ascii_lowercase(),
+        None => return false,
+    };
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    // gcc-12, clang++-15, clang-15.0
+    let name = match name.rsplit_once('-') {
+        Some((name, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => name,
+        _ => name,
+    };
+    COMPILER_NAMES.iter().any(|compiler| {
+        name == *compiler
+            || name
+                .strip_suffix(compiler)
+                .is_some_and(|prefix| prefix.ends_with('-') && *compiler != "cl")
+    })
+}
+
+pub(crate) fn is_source(arg: &str) -> bool {
+    !arg.starts_with('-')
+        && Path::new(arg)
+            .extension()
+            .and_then(|v| v.to_str())
+            .is_some_and(|v| SOURCE_EXTENSIONS.contains(&v))
+}
+
+/// Returns the directory make prints with `-w`, i.e. `make[1]: Entering directory '/path'`.
+fn make_directory<'a>(line: &'a str, action: &str) -> Option<&'a str> {
+    let (_, path) = line.split_once(action)?;
+    let path = path.trim().strip_prefix(['\'', '`'])?;
+    path.strip_suffix('\'')
+}
+
+/// Removes the progress `[3/10] ` Ninja prints before each command.
+fn strip_ninja_progress(line: &str) -> &str {
+    let rest = match line.strip_prefix('[').and_then(|v| v.split_once(']')) {
+        Some((progress, rest)) if progress.split('/').all(|v| v.parse::<usize>().is_ok()) => rest,
+        _ => return line,
+    };
+    rest.trim_start()
+}
+
+/// Returns compile commands for each source of a compiler invocation.
+fn compile_commands(args: &[String], directory: &Path) -> Vec<CompileCommand> {
+    // Environment variables, and prefixes such as `libtool: compile:`
+    let skip = args
+        .iter()
+        .take_while(|v| {
+            v.ends_with(':')
+                || v.split_once('=')
+                    .is_some_and(|(name, _)| !name.is_empty() && !name.starts_with('-'))
+        })
+        .count();
+    let args = remove_launchers(&args[skip..], &[]);
+    if args.is_empty() || !is_compiler(&args[0]) {
+        return Vec::new();
+    }
+    if !args.iter().any(|v| v == "-c" || v == "/c") {
+        trace!("compile_commands: not a compilation: {:?}", args);
+        return Vec::new();
+    }
+    let sources: Vec<usize> = (1..args.len())
+        .filter(|i| is_source(&args[*i]) && args[*i - 1] != "-o")
+        .collect();
+    sources
+        .iter()
+        .map(|i| {
+            let own: Vec<String> = args
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| j == i || !sources.contains(j))
+                .map(|(_, v)| v.clone())
+                .collect();
+            CompileCommand {
+                directory: directory.to_path_buf(),
+                command: None,
+                file: PathBuf::from(&args[*i]),
+                output: output_argument(&own).map(PathBuf::from),
+                arguments: Some(own),
+            }
+        })
+        .collect()
+}
+
+/// Recognizes compiler invocations in a build log and returns compile commands for them.
+///
+/// Commands run in `directory` unless make says it enters another one with `-w`, or the command
+/// starts with `cd <dir> &&`. A command compiling several sources yields one entry for each.
+pub fn parse_build_log(content: &str, directory: &Path) -> Vec<CompileCommand> {
+    let mut directories = vec![directory.to_path_buf()];
+    let mut result = Vec::new();
+    let mut logical_line = String::new();
+    for line in content.lines() {
+        if let Some(line) = line.strip_suffix('\\') {
+            logical_line.push_str(line);
+            continue;
+        }
+        logical_line.push_str(line);
+        let line = std::mem::take(&mut logical_line);
+
+        if let Some(path) = make_directory(&line, "Entering directory") {
+            let path = directories.last().unwrap().join(path);
+            directories.push(path);
+            continue;
+        }
+        if make_directory(&line, "Leaving directory").is_some() {
+            if directories.len() > 1 {
+                directories.pop();
+            }
+            continue;
+        }
+
+        let args = match shell_words::split(strip_ninja_progress(line.trim())) {
+            Ok(args) => args,
+            Err(why) => {
+                trace!("parse_build_log: skip {:?}: {}", line, why);
+                continue;
+            }
+        };
+        // `a; b` is split into `a;` and `b`
+        let args: Vec<String> = args
+            .into_iter()
+            .flat_map(|v| match v.strip_suffix(';') {
+                Some(rest) if !rest.is_empty() => vec![String::from(rest), String::from(";")],
+                _ => vec![v],
+            })
+            .collect();
+        let mut directory = directories.last().unwrap().clone();
+        for command in args.split(|v| v == "&&" || v == ";" || v == "||") {
+            match command {
+                [] => (),
+                [cd, path] if cd == "cd" => directory = directory.join(path),
+                _ => result.extend(compile_commands(command, &directory)),
+            }
+        }
+    }
+    result
+}
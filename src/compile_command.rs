@@ -0,0 +1,217 @@
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+use crate::error::{Error, Result};
+
+/// An entry of `compile_commands.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompileCommand {
+    /// Working directory of the compilation.
+    pub directory: PathBuf,
+    /// Compile command as a single shell-escaped string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Compile command as a list of arguments. Preferred over `command`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Vec<String>>,
+    /// Main translation unit source.
+    pub file: PathBuf,
+    /// Output of the compilation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+}
+
+impl CompileCommand {
+    /// Returns `arguments`, or `command` split into arguments.
+    pub fn arguments(&self) -> Result<Vec<String>> {
+        let args = if let Some(ref arguments) = self.arguments {
+            arguments.clone()
+        } else if let Some(ref command) = self.command {
+            shell_words::split(command)?
+        } else {
+            return Err(Error::CommandFormatError);
+        };
+        if args.is_empty() {
+            return Err(Error::CommandFormatError);
+        }
+        Ok(args)
+    }
+
+    /// Returns the path of `file`, which may be relative to `directory`.
+    pub fn path(&self) -> PathBuf {
+        self.directory.join(&self.file)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.directory.as_os_str().is_empty() {
+            return Err(Error::EntryFormatError("`directory` is empty"));
+        }
+        if self.file.as_os_str().is_empty() {
+            return Err(Error::EntryFormatError("`file` is empty"));
+        }
+        self.arguments().map(|_| ())
+    }
+
+    /// Returns `command`, or `arguments` joined into a shell command.
+    pub fn command_line(&self) -> Result<String> {
+        if let Some(ref command) = self.command {
+            Ok(command.clone())
+        } else {
+            Ok(shell_words::join(self.arguments()?))
+        }
+    }
+}
+
+/// Number of decoded entries [`read_compile_commands`] buffers ahead of the consumer.
+const STREAM_BUFFER_SIZE: usize = 1024;
+
+/// Validates the `index`-th entry and resolves its relative `directory` against `base`.
+fn entry(index: usize, value: serde_json::Value, base: &Path) -> Result<CompileCommand> {
+    let mut command: CompileCommand = serde_json::from_value(value)
+        .map_err(|why| Error::InvalidEntryError(index, Box::new(why.into())))?;
+    command
+        .validate()
+        .map_err(|why| Error::InvalidEntryError(index, Box::new(why)))?;
+    if command.directory.is_relative() {
+        command.directory = base.join(&command.directory);
+    }
+    Ok(command)
+}
+
+/// Passes entries of the array to the callback one at a time, until it returns `false`.
+struct EntryVisitor<'a, F> {
+    base: &'a Path,
+    callback: F,
+}
+
+impl<'de, 'a, F> Visitor<'de> for EntryVisitor<'a, F>
+where
+    F: FnMut(Result<CompileCommand>) -> bool,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of compile commands")
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> std::result::Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut index = 0;
+        while let Some(value) = seq.next_element::<serde_json::Value>()? {
+            if !(self.callback)(entry(index, value, self.base)) {
+                break;
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `compile_commands.json` from `reader` entry by entry, without holding the document in
+/// memory, and passes each entry to `callback` until it returns `false`.
+///
+/// See [`parse_compile_commands`] for how entries are validated and resolved. Entries decoded
+/// before a syntax error are passed as usual.
+pub fn for_each_compile_command<R, F>(reader: R, base: &Path, callback: F) -> Result<()>
+where
+    R: Read,
+    F: FnMut(Result<CompileCommand>) -> bool,
+{
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    let mut finished = true;
+    let result = deserializer.deserialize_seq(EntryVisitor {
+        base: &base,
+        callback: {
+            let mut callback = callback;
+            let finished = &mut finished;
+            move |entry| {
+                *finished = callback(entry);
+                *finished
+            }
+        },
+    });
+    // The rest of the document is not read if the callback stops
+    if finished {
+        result?;
+        deserializer.end()?;
+    }
+    Ok(())
+}
+
+/// Entries of `compile_commands.json` decoded in a background thread, so that the consumer can
+/// start on the first entries while the rest are still being read. See
+/// [`read_compile_commands`].
+pub struct CompileCommandStream {
+    receiver: Receiver<Result<CompileCommand>>,
+}
+
+impl Iterator for CompileCommandStream {
+    type Item = Result<CompileCommand>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Reads `compile_commands.json` from `reader` in a background thread and yields its entries as
+/// they are decoded. A syntax error of the document is yielded after the entries before it.
+///
+/// At most a fixed number of decoded entries wait for the consumer. Memory for the entries
+/// themselves is up to the consumer, e.g.
+/// [`dump_dependencies_streaming`](crate::dump_dependencies_streaming) does not keep them.
+pub fn read_compile_commands<R>(reader: R, base: &Path) -> CompileCommandStream
+where
+    R: Read + Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel(STREAM_BUFFER_SIZE);
+    let base = base.to_path_buf();
+    thread::spawn(move || {
+        let result = for_each_compile_command(reader, &base, |entry| sender.send(entry).is_ok());
+        if let Err(why) = result {
+            let _ = sender.send(Err(why));
+        }
+    });
+    CompileCommandStream { receiver }
+}
+
+/// Parses `compile_commands.json`. Entries are validated one by one, so that a malformed entry
+/// does not reject the others.
+///
+/// Relative `directory` is resolved against `base`, the directory of the database. Relative `file`
+/// and `output` are relative to `directory` as the format specifies; see [`CompileCommand::path`].
+pub fn parse_compile_commands(content: &str, base: &Path) -> Result<Vec<Result<CompileCommand>>> {
+    let mut result = Vec::new();
+    for_each_compile_command(content.as_bytes(), base, |entry| {
+        result.push(entry);
+        true
+    })?;
+    Ok(result)
+}
+
+/// Returns the directory relative `directory`s in the database at `path` are resolved against.
+pub(crate) fn database_base(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Reads `compile_commands.json` at `path`. See [`parse_compile_commands`].
+pub fn load_compile_commands(path: &Path) -> Result<Vec<Result<CompileCommand>>> {
+    let mut result = Vec::new();
+    for_each_compile_command(File::open(path)?, database_base(path), |entry| {
+        result.push(entry);
+        true
+    })?;
+    Ok(result)
+}
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use crate::compile_command::CompileCommand;
+use crate::dependency::{dump_dependency, dump_user_dependency, DumpOptions};
+use crate::error::{Error, Result};
+use crate::include_tree::{dump_include_tree, Include};
+use crate::msvc::Msvc;
+use crate::p1689::{dump_modules, ModuleDependencies};
+
+/// Family of compiler drivers. Determines how to ask the compiler for dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompilerFamily {
+    /// gcc, clang and compatible drivers. Use `-M` and `-H`.
+    Gcc,
+    /// cl and clang-cl. Use `/showIncludes`.
+    Msvc,
+}
+
+impl CompilerFamily {
+    /// Detects the family from the compiler name and `--driver-mode`.
+    pub fn detect(args: &[String]) -> Self {
+        if let Some(mode) = args
+            .iter()
+            .rev()
+            .find_map(|v| v.strip_prefix("--driver-mode="))
+        {
+            return if mode == "cl" {
+                CompilerFamily::Msvc
+            } else {
+                CompilerFamily::Gcc
+            };
+        }
+        let name = args
+            .first()
+            .and_then(|v| Path::new(v).file_stem())
+            .and_then(|v| v.to_str())
+            .map(str::to_ascii_lowercase);
+        match name.as_deref() {
+            Some("cl" | "clang-cl") => CompilerFamily::Msvc,
+            _ => CompilerFamily::Gcc,
+        }
+    }
+
+    /// Returns the implementation of this family configured by `options`.
+    pub fn compiler(self, options: &DumpOptions) -> Box<dyn Compiler> {
+        match self {
+            CompilerFamily::Gcc => Box::new(Gcc),
+            CompilerFamily::Msvc => Box::new(Msvc {
+                include_prefix: options
+                    .msvc_include_prefix
+                    .clone()
+                    .unwrap_or_else(|| String::from(Msvc::DEFAULT_INCLUDE_PREFIX)),
+            }),
+        }
+    }
+}
+
+/// Way to ask a compiler for dependencies of a compile command.
+pub trait Compiler: Send + Sync {
+    /// Returns canonicalized dependencies including the source file itself.
+    ///
+    /// If `user_headers_only` is set, the compiler may omit system headers.
+    fn dependencies(
+        &self,
+        command: &CompileCommand,
+        user_headers_only: bool,
+    ) -> Result<Vec<PathBuf>>;
+
+    /// Returns headers included by the source file and headers they include.
+    fn include_tree(&self, command: &CompileCommand) -> Result<Vec<Include>>;
+
+    /// Returns C++20 modules the source file provides and imports.
+    fn modules(&self, _command: &CompileCommand) -> Result<ModuleDependencies> {
+        Err(Error::UnsupportedError("C++20 module scanning"))
+    }
+}
+
+/// gcc, clang and compatible drivers.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Gcc;
+
+impl Compiler for Gcc {
+    fn dependencies(
+        &self,
+        command: &CompileCommand,
+        user_headers_only: bool,
+    ) -> Result<Vec<PathBuf>> {
+        if user_headers_only {
+            dump_user_dependency(command)
+        } else {
+            dump_dependency(command)
+        }
+    }
+
+    fn include_tree(&self, command: &CompileCommand) -> Result<Vec<Include>> {
+        dump_include_tree(command)
+    }
+
+    fn modules(&self, command: &CompileCommand) -> Result<ModuleDependencies> {
+        dump_modules(command)
+    }
+}
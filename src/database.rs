@@ -0,0 +1,231 @@
+//! Where compile commands come from: `compile_commands.json` files, build directories, stdin and
+//! `compile_flags.txt`.
+use log::trace;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::build_log::is_source;
+use crate::compile_command::{
+    database_base, for_each_compile_command, load_compile_commands, read_compile_commands,
+    CompileCommand,
+};
+use crate::error::{Error, Result};
+
+pub const COMPILE_COMMANDS_FILE_NAME: &str = "compile_commands.json";
+pub const COMPILE_FLAGS_FILE_NAME: &str = "compile_flags.txt";
+
+/// Returns the database in the build directory `directory`, like clangd does.
+///
+/// `compile_commands.json` is preferred over `compile_flags.txt`, and the directory itself over its
+/// `build` subdirectory.
+pub fn find_database(directory: &Path) -> Result<PathBuf> {
+    for directory in [directory.to_path_buf(), directory.join("build")] {
+        for name in [COMPILE_COMMANDS_FILE_NAME, COMPILE_FLAGS_FILE_NAME] {
+            let path = directory.join(name);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+    }
+    Err(Error::DatabaseNotFoundError(directory.to_path_buf()))
+}
+
+/// Collects files under `directory` whose names `matches` accepts, in path order.
+///
+/// Hidden entries and symbolic links are skipped.
+fn find_files<F>(directory: &Path, matches: &F, result: &mut Vec<PathBuf>) -> io::Result<()>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut entries = fs::read_dir(directory)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|v| v.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_files(&entry.path(), matches, result)?;
+        } else if file_type.is_file() && name.to_str().is_some_and(matches) {
+            result.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Returns all `compile_commands.json` under `root` in path order.
+///
+/// Hidden directories are skipped. Symbolic links are not followed, so that a link to the database
+/// in the build directory, common at the top of projects, is not counted twice.
+pub fn discover_databases(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    find_files(
+        root,
+        &|name| name == COMPILE_COMMANDS_FILE_NAME,
+        &mut result,
+    )?;
+    Ok(result)
+}
+
+/// Parses `compile_flags.txt`, one flag per line, and applies the flags to every source under
+/// `directory`.
+///
+/// Commands run in `directory` with `cc` for C sources and `c++` for the others.
+pub fn parse_compile_flags(content: &str, directory: &Path) -> Result<Vec<CompileCommand>> {
+    let flags: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect();
+    let mut sources = Vec::new();
+    find_files(directory, &is_source, &mut sources)?;
+    trace!("parse_compile_flags: {} sources", sources.len());
+    let result = sources
+        .into_iter()
+        .map(|path| {
+            let file = path.strip_prefix(directory).unwrap_or(&path).to_path_buf();
+            let compiler = match file.extension().and_then(|v| v.to_str()) {
+                Some("c") => "cc",
+                _ => "c++",
+            };
+            let mut arguments = vec![String::from(compiler)];
+            arguments.extend(flags.iter().cloned());
+            arguments.extend([String::from("-c"), file.display().to_string()]);
+            CompileCommand {
+                directory: directory.to_path_buf(),
+                command: None,
+                arguments: Some(arguments),
+                file,
+                output: None,
+            }
+        })
+        .collect();
+    Ok(result)
+}
+
+/// Loads compile commands from `path`.
+///
+/// `path` may be `compile_commands.json`, `compile_flags.txt`, a build directory containing either
+/// of them (see [`find_database`]), or `-` to read `compile_commands.json` from stdin, whose
+/// relative directories are resolved against the current directory.
+pub fn load_database(path: &Path) -> Result<Vec<Result<CompileCommand>>> {
+    if path == Path::new("-") {
+        let mut result = Vec::new();
+        for_each_compile_command(io::stdin(), &env::current_dir()?, |entry| {
+            result.push(entry);
+            true
+        })?;
+        return Ok(result);
+    }
+    let path = resolve_database(path)?;
+    if is_compile_flags(&path) {
+        return Ok(load_compile_flags(&path)?.into_iter().map(Ok).collect());
+    }
+    load_compile_commands(&path)
+}
+
+/// Like [`load_database`], but `compile_commands.json` is decoded in a background thread and its
+/// entries are yielded as they are decoded. See [`read_compile_commands`].
+///
+/// A syntax error of the document is yielded after the entries before it, unlike
+/// [`load_database`].
+pub fn stream_database(
+    path: &Path,
+) -> Result<Box<dyn Iterator<Item = Result<CompileCommand>> + Send>> {
+    if path == Path::new("-") {
+        return Ok(Box::new(read_compile_commands(
+            io::stdin(),
+            &env::current_dir()?,
+        )));
+    }
+    let path = resolve_database(path)?;
+    if is_compile_flags(&path) {
+        return Ok(Box::new(load_compile_flags(&path)?.into_iter().map(Ok)));
+    }
+    let file = File::open(&path)?;
+    Ok(Box::new(read_compile_commands(file, database_base(&path))))
+}
+
+fn resolve_database(path: &Path) -> Result<PathBuf> {
+    let path = if path.is_dir() {
+        find_database(path)?
+    } else {
+        path.to_path_buf()
+    };
+    trace!("resolve_database: {:?}", path);
+    Ok(path)
+}
+
+fn is_compile_flags(path: &Path) -> bool {
+    path.file_name().and_then(|v| v.to_str()) == Some(COMPILE_FLAGS_FILE_NAME)
+}
+
+fn load_compile_flags(path: &Path) -> Result<Vec<CompileCommand>> {
+    let content = fs::read_to_string(path)?;
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+        _ => env::current_dir()?,
+    };
+    parse_compile_flags(&content, &directory)
+}
+
+/// Source file compiled differently by more than one database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// `CompileCommand::path` of the source file. Canonicalized if exists.
+    pub file: PathBuf,
+    /// Indices of the databases in the order given.
+    pub databases: Vec<usize>,
+}
+
+/// Concatenates `databases` into one, and returns it with the conflicts between them.
+///
+/// Entries identical to earlier ones are dropped. Conflicting entries are all kept as variants.
+pub fn merge_databases(
+    databases: Vec<Vec<CompileCommand>>,
+) -> (Vec<CompileCommand>, Vec<Conflict>) {
+    let mut result: Vec<CompileCommand> = Vec::new();
+    let mut origins: HashMap<PathBuf, Vec<(usize, usize)>> = HashMap::new();
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    for (database, commands) in databases.into_iter().enumerate() {
+        for command in commands {
+            let file = command.path();
+            let file = file.canonicalize().unwrap_or(file);
+            let entries = origins.entry(file.clone()).or_default();
+            let duplicated = entries.iter().any(|(_, i)| {
+                let other = &result[*i];
+                other.directory == command.directory
+                    && other.output == command.output
+                    && other.arguments().ok() == command.arguments().ok()
+            });
+            if duplicated {
+                trace!("merge_databases: duplicated entry for {:?}", file);
+                continue;
+            }
+            if entries.iter().any(|(other, _)| *other != database) {
+                match conflicts.iter_mut().find(|v| v.file == file) {
+                    Some(conflict) => {
+                        if !conflict.databases.contains(&database) {
+                            conflict.databases.push(database);
+                        }
+                    }
+                    None => {
+                        let mut databases: Vec<usize> = entries.iter().map(|v| v.0).collect();
+                        databases.push(database);
+                        databases.dedup();
+                        conflicts.push(Conflict { file, databases });
+                    }
+                }
+            }
+            entries.push((database, result.len()));
+            result.push(command);
+        }
+    }
+    (result, conflicts)
+}
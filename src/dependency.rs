@@ -0,0 +1,430 @@
+#[allow(unused_imports)]
+use log::{info, trace, warn};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Write;
+#[allow(unused_imports)]
+use std::io::{BufRead, Read};
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::arguments::{remove_dependency_flags, remove_launchers, remove_output_flags};
+use crate::build_depfile::read_build_depfile;
+use crate::compile_command::CompileCommand;
+use crate::compiler::{Compiler, CompilerFamily};
+use crate::depfile::parse_depfile;
+use crate::error::{Error, Result};
+use crate::include_tree::{flatten_includes, Include};
+use crate::ninja_deps::ninja_dependencies;
+use crate::p1689::ModuleDependencies;
+use crate::scan_deps::scan_dependencies;
+use crate::scanner::{ScanMode, Scanner};
+use crate::system_header::{SystemDirectories, FALLBACK_SYSTEM_DIRECTORIES};
+use crate::variant::variant_labels;
+
+/// Classification of a dependency.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    /// Header of the toolchain or the system.
+    System,
+    /// File of the project.
+    Project,
+    /// File generated in the build directory.
+    Generated,
+}
+
+/// Dependencies of a single translation unit.
+#[derive(Debug)]
+pub struct TranslationUnit {
+    /// Source file of the translation unit as written (`CompileCommand::file`). May be relative
+    /// to `directory`.
+    pub file: PathBuf,
+    /// `file` resolved against `directory`. Canonicalized if exists.
+    pub path: PathBuf,
+    /// Working directory of the compilation (`CompileCommand::directory`). Canonicalized if exists.
+    pub directory: PathBuf,
+    /// Output of the compilation (`CompileCommand::output`).
+    pub output: Option<PathBuf>,
+    /// Tells apart commands for the same file. `None` if the file is compiled once.
+    pub variant: Option<String>,
+    /// Canonicalized dependencies. Empty if `error` is set.
+    pub dependencies: Vec<PathBuf>,
+    /// Why the dependencies could not be extracted.
+    pub error: Option<Error>,
+    /// Include tree. Given if `DumpOptions::include_tree` is set.
+    pub includes: Option<Vec<Include>>,
+    /// Whether dependencies come from a depfile older than some of them.
+    pub stale: bool,
+    /// C++20 modules. Given if `DumpOptions::modules` is set.
+    pub modules: Option<ModuleDependencies>,
+    /// Canonicalized system include directories.
+    pub system_directories: Vec<PathBuf>,
+    source: Option<PathBuf>,
+}
+
+impl TranslationUnit {
+    pub fn new(command: &CompileCommand, result: Result<Vec<PathBuf>>) -> Self {
+        let (dependencies, error) = match result {
+            Ok(dependencies) => (dependencies, None),
+            Err(error) => (Vec::new(), Some(error)),
+        };
+        let directory = command
+            .directory
+            .canonicalize()
+            .unwrap_or_else(|_| command.directory.clone());
+        let path = directory.join(&command.file);
+        let source = path.canonicalize().ok();
+        Self {
+            file: command.file.clone(),
+            path: source.clone().unwrap_or(path),
+            directory,
+            output: command.output.clone(),
+            variant: None,
+            dependencies,
+            error,
+            includes: None,
+            stale: false,
+            modules: None,
+            system_directories: FALLBACK_SYSTEM_DIRECTORIES
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+            source,
+        }
+    }
+
+    /// Canonical path of the source file, if exists.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Classifies a dependency of this translation unit.
+    ///
+    /// Files in the build directory are regarded as generated unless the source itself is in there.
+    pub fn classify(&self, path: &Path) -> DependencyKind {
+        if self.system_directories.iter().any(|v| path.starts_with(v)) {
+            return DependencyKind::System;
+        }
+        let in_source_build = match self.source {
+            Some(ref source) => source.starts_with(&self.directory),
+            None => true,
+        };
+        if !in_source_build && path.starts_with(&self.directory) {
+            DependencyKind::Generated
+        } else {
+            DependencyKind::Project
+        }
+    }
+}
+
+/// Parses output of `-M` and returns existing dependencies as canonical paths.
+///
+/// Relative paths are resolved against `directory`.
+pub fn parse_dependency<R: Read>(
+    mut output: BufReader<R>,
+    directory: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut content = String::new();
+    output.read_to_string(&mut content)?;
+    let depfile = parse_depfile(&content)?;
+    resolve_dependencies(depfile.prerequisites(), directory)
+}
+
+/// Resolves `paths` against `directory` and returns existing ones as canonical paths.
+pub(crate) fn resolve_dependencies(paths: Vec<&Path>, directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    for path in paths {
+        let path = directory.join(path);
+        if path.exists() {
+            result.push(path.canonicalize()?);
+        } else {
+            trace!("resolve_dependencies: not found: {:?}", path);
+        }
+    }
+    if result.is_empty() {
+        warn!("No dependency found");
+    }
+    Ok(result)
+}
+
+/// Returns arguments of `command` with `flags` inserted.
+///
+/// Flags that generate dependency information or name output files are removed, so that the run
+/// never writes into the build tree.
+pub(crate) fn rewrite_arguments(command: &CompileCommand, flags: &[&str]) -> Result<Vec<String>> {
+    let args = remove_launchers(&command.arguments()?, &[]);
+    trace!("rewrite_arguments: args={:?}", args);
+    let mut args = remove_output_flags(&remove_dependency_flags(&args));
+
+    for (i, flag) in flags.iter().enumerate() {
+        args.insert(1 + i, flag.to_string());
+    }
+    Ok(args)
+}
+
+/// Returns a path in the temporary directory unique to this call.
+pub(crate) fn temporary_path(name: &str) -> PathBuf {
+    static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+    env::temp_dir().join(format!(
+        "dump-dependency-{}-{}-{}",
+        process::id(),
+        SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        name
+    ))
+}
+
+/// Runs the compiler in the working directory of `command`.
+pub(crate) fn run_compiler(command: &CompileCommand, args: &[String]) -> Result<Output> {
+    Ok(Command::new(&args[0])
+        .args(&args[1..])
+        .current_dir(&command.directory)
+        .output()?)
+}
+
+/// Runs the gcc-style compile command with `-M` and returns its dependencies.
+pub fn dump_dependency(command: &CompileCommand) -> Result<Vec<PathBuf>> {
+    dump_make_dependency(command, "-M")
+}
+
+/// Runs the gcc-style compile command with `-MM` and returns its dependencies except system headers.
+pub fn dump_user_dependency(command: &CompileCommand) -> Result<Vec<PathBuf>> {
+    dump_make_dependency(command, "-MM")
+}
+
+fn dump_make_dependency(command: &CompileCommand, flag: &str) -> Result<Vec<PathBuf>> {
+    let args = rewrite_arguments(command, &[flag])?;
+    let output = run_compiler(command, &args)?;
+    if !output.stderr.is_empty() {
+        // Tell human that a error occured
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(&output.stderr)?;
+    }
+    output.status.exit_ok()?;
+
+    parse_dependency(
+        BufReader::new(Cursor::new(output.stdout)),
+        &command.directory,
+    )
+}
+
+/// Source of dependencies.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Run the compiler of each compile command.
+    #[default]
+    Compiler,
+    /// Run this `clang-scan-deps` once for all compile commands.
+    ///
+    /// Falls back to `Compiler` for compile commands it fails to scan, and in include tree mode.
+    ClangScanDeps(PathBuf),
+    /// Scan sources and headers with the built-in [`Scanner`]. Never runs compilers.
+    Scanner(ScanMode),
+    /// Read depfiles the build left in the build tree. Never runs compilers.
+    ///
+    /// The pattern is tried in addition to `-MF` and the output; see
+    /// [`depfile_candidates`](crate::depfile_candidates).
+    BuildDepfiles(Option<String>),
+    /// Read `.ninja_deps` of a Ninja build. Never runs compilers.
+    ///
+    /// Uses this log for all compile commands if given, otherwise `.ninja_deps` in the directory
+    /// of each compile command.
+    NinjaDeps(Option<PathBuf>),
+}
+
+/// Options of [`dump_dependencies`].
+#[derive(Debug, Default, Clone)]
+pub struct DumpOptions {
+    pub backend: Backend,
+    /// Ask the compiler for include trees and fill `TranslationUnit::includes`.
+    pub include_tree: bool,
+    /// Directories regarded as system include directories in addition to what compilers tell.
+    pub system_prefixes: Vec<PathBuf>,
+    /// Ask the compiler for user headers only, e.g. use `-MM` instead of `-M`.
+    ///
+    /// Does not apply to `include_tree`, which always lists all headers.
+    pub user_headers_only: bool,
+    /// Compiler launchers to skip in addition to [`KNOWN_LAUNCHERS`](crate::arguments::KNOWN_LAUNCHERS).
+    pub launchers: Vec<String>,
+    /// Prefix of `/showIncludes` lines for localized cl. Defaults to `Note: including file:`.
+    pub msvc_include_prefix: Option<String>,
+    /// Ask the compiler for C++20 module dependencies in P1689 format and fill
+    /// `TranslationUnit::modules`.
+    pub modules: bool,
+}
+
+fn dump_translation_unit(
+    command: &CompileCommand,
+    compiler: &dyn Compiler,
+    options: &DumpOptions,
+) -> TranslationUnit {
+    if !options.include_tree {
+        let result = compiler.dependencies(command, options.user_headers_only);
+        return TranslationUnit::new(command, result);
+    }
+    match compiler.include_tree(command) {
+        Ok(includes) => {
+            let dependencies = flatten_includes(command, &includes);
+            let mut unit = TranslationUnit::new(command, Ok(dependencies));
+            unit.includes = Some(includes);
+            unit
+        }
+        Err(why) => TranslationUnit::new(command, Err(why)),
+    }
+}
+
+fn dump(
+    command: &CompileCommand,
+    scanned: Option<Vec<PathBuf>>,
+    options: &DumpOptions,
+    system_directories: &SystemDirectories,
+) -> TranslationUnit {
+    let args = match command.arguments() {
+        Ok(args) => remove_launchers(&args, &options.launchers),
+        Err(why) => return TranslationUnit::new(command, Err(why)),
+    };
+    let compiler: Box<dyn Compiler> = match options.backend {
+        Backend::Scanner(mode) => Box::new(Scanner { mode }),
+        _ => CompilerFamily::detect(&args).compiler(options),
+    };
+    let command = &CompileCommand {
+        command: None,
+        arguments: Some(args),
+        ..command.clone()
+    };
+    let mut unit = match (scanned, &options.backend) {
+        (Some(dependencies), _) => TranslationUnit::new(command, Ok(dependencies)),
+        (None, Backend::BuildDepfiles(_) | Backend::NinjaDeps(_)) if options.include_tree => {
+            TranslationUnit::new(
+                command,
+                Err(Error::UnsupportedError("include tree from depfiles")),
+            )
+        }
+        (None, Backend::BuildDepfiles(pattern)) => {
+            match read_build_depfile(command, pattern.as_deref()) {
+                Ok(depfile) => {
+                    let mut unit = TranslationUnit::new(command, Ok(depfile.dependencies));
+                    unit.stale = depfile.stale;
+                    unit
+                }
+                Err(why) => TranslationUnit::new(command, Err(why)),
+            }
+        }
+        (None, Backend::NinjaDeps(_)) => {
+            TranslationUnit::new(command, Err(Error::NinjaDepsNotFoundError))
+        }
+        (None, _) => dump_translation_unit(command, compiler.as_ref(), options),
+    };
+    if options.modules && unit.error.is_none() {
+        match compiler.modules(command) {
+            Ok(modules) => unit.modules = Some(modules),
+            Err(why) => warn!("Failed to scan modules of {:?}: {}", command.file, why),
+        }
+    }
+    match system_directories.get(command) {
+        Ok(directories) => unit.system_directories = directories,
+        Err(why) => warn!("Failed to get system include directories: {}", why),
+    }
+    if options.user_headers_only && !options.include_tree {
+        let (system, user): (Vec<_>, Vec<_>) = mem::take(&mut unit.dependencies)
+            .into_iter()
+            .partition(|path| unit.classify(path) == DependencyKind::System);
+        for path in system {
+            info!(
+                "System header is reported by the compiler as user header: {:?}",
+                path
+            );
+        }
+        unit.dependencies = user;
+    }
+    unit
+}
+
+fn system_directories(options: &DumpOptions) -> SystemDirectories {
+    let system_directories = SystemDirectories::new(options.system_prefixes.clone());
+    match options.backend {
+        Backend::Scanner(_) | Backend::BuildDepfiles(_) | Backend::NinjaDeps(_) => {
+            system_directories.offline()
+        }
+        _ => system_directories,
+    }
+}
+
+/// Extracts dependencies of each command in parallel.
+///
+/// Results are returned in the same order as `commands`. Commands for the same file are kept and
+/// labeled in `TranslationUnit::variant`. See [`merge_variants`](crate::merge_variants).
+pub fn dump_dependencies(
+    commands: &[CompileCommand],
+    options: &DumpOptions,
+) -> Vec<TranslationUnit> {
+    let system_directories = system_directories(options);
+    let mut scanned = match options.backend {
+        Backend::ClangScanDeps(ref executable) if !options.include_tree => {
+            scan_dependencies(commands, executable, &options.launchers).unwrap_or_else(|why| {
+                warn!(
+                    "clang-scan-deps failed. Fall back to the compilers: {}",
+                    why
+                );
+                Vec::new()
+            })
+        }
+        Backend::NinjaDeps(ref log) if !options.include_tree => {
+            ninja_dependencies(commands, log.as_deref())
+        }
+        _ => Vec::new(),
+    };
+    scanned.resize(commands.len(), None);
+    let labels = variant_labels(commands);
+    commands
+        .par_iter()
+        .zip(scanned)
+        .zip(labels)
+        .map(|((command, scanned), label)| {
+            trace!("file={:?}", command.file);
+            let mut unit = dump(command, scanned, options, &system_directories);
+            unit.variant = label;
+            unit
+        })
+        .collect()
+}
+
+/// Like [`dump_dependencies`], but starts on each command as soon as `commands` yields it, e.g.
+/// while [`read_compile_commands`](crate::read_compile_commands) is still decoding the rest.
+///
+/// Neither commands nor results are kept. `consume` is called with the index of each command, the
+/// command and its result in the order they finish. Variants are not labeled; keep the commands
+/// and see [`label_variants`](crate::label_variants) if needed. Backends that scan all commands
+/// at once wait for the last command.
+pub fn dump_dependencies_streaming<I, F>(commands: I, options: &DumpOptions, consume: F)
+where
+    I: Iterator<Item = CompileCommand> + Send,
+    F: FnMut(usize, CompileCommand, TranslationUnit) + Send,
+{
+    let consume = Mutex::new(consume);
+    if let Backend::ClangScanDeps(_) | Backend::NinjaDeps(_) = options.backend {
+        let commands: Vec<_> = commands.collect();
+        let units = dump_dependencies(&commands, options);
+        let mut consume = consume.into_inner().unwrap();
+        for (i, (command, mut unit)) in commands.into_iter().zip(units).enumerate() {
+            unit.variant = None;
+            consume(i, command, unit);
+        }
+        return;
+    }
+    let system_directories = system_directories(options);
+    commands.enumerate().par_bridge().for_each(|(i, command)| {
+        trace!("file={:?}", command.file);
+        let unit = dump(&command, None, options, &system_directories);
+        (consume.lock().unwrap())(i, command, unit);
+    });
+}
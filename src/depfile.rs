@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// A rule of a Make depfile: `targets: prerequisites`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub targets: Vec<PathBuf>,
+    pub prerequisites: Vec<PathBuf>,
+}
+
+/// Make depfile as written by `-M`, `-MD` and friends.
+///
+/// Phony rules emitted by `-MP` are kept as rules without prerequisites.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Depfile {
+    pub rules: Vec<Rule>,
+}
+
+impl Depfile {
+    /// Targets of all rules that have prerequisites.
+    pub fn targets(&self) -> Vec<&Path> {
+        let mut result: Vec<&Path> = Vec::new();
+        for rule in self.rules.iter().filter(|v| !v.prerequisites.is_empty()) {
+            for target in rule.targets.iter() {
+                if !result.contains(&target.as_path()) {
+                    result.push(target);
+                }
+            }
+        }
+        result
+    }
+
+    /// Prerequisites of all rules without duplicates, in order of appearance.
+    pub fn prerequisites(&self) -> Vec<&Path> {
+        let mut result: Vec<&Path> = Vec::new();
+        for rule in self.rules.iter() {
+            for prerequisite in rule.prerequisites.iter() {
+                if !result.contains(&prerequisite.as_path()) {
+                    result.push(prerequisite);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Colon,
+    Newline,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    tokens: Vec<Token>,
+    word: String,
+    // Only the first `:` of a logical line separates targets from prerequisites
+    seen_colon: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            tokens: Vec::new(),
+            word: String::new(),
+            seen_colon: false,
+        }
+    }
+
+    fn end_word(&mut self) {
+        if !self.word.is_empty() {
+            self.tokens
+                .push(Token::Word(std::mem::take(&mut self.word)));
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.end_word();
+        self.tokens.push(Token::Newline);
+        self.seen_colon = false;
+    }
+
+    fn is_separator(c: Option<&char>) -> bool {
+        matches!(c, None | Some(' ' | '\t' | '\r' | '\n'))
+    }
+
+    fn backslashes(&mut self) {
+        let mut n = 1;
+        while self.chars.next_if_eq(&'\\').is_some() {
+            n += 1;
+        }
+        match self.chars.peek() {
+            // Line continuation
+            Some('\n') => {
+                self.chars.next();
+                self.word.extend(std::iter::repeat_n('\\', n - 1));
+                self.end_word();
+            }
+            Some('\r') => {
+                self.chars.next();
+                if self.chars.next_if_eq(&'\n').is_some() {
+                    self.word.extend(std::iter::repeat_n('\\', n - 1));
+                    self.end_word();
+                } else {
+                    self.word.extend(std::iter::repeat_n('\\', n));
+                    self.word.push('\r');
+                }
+            }
+            // 2N+1 backslashes followed by a space is N backslashes and an escaped space.
+            // 2N backslashes followed by a space is 2N backslashes and end of the word.
+            Some(&c @ (' ' | '#')) => {
+                if n % 2 == 1 {
+                    self.chars.next();
+                    self.word.extend(std::iter::repeat_n('\\', n / 2));
+                    self.word.push(c);
+                } else {
+                    self.word.extend(std::iter::repeat_n('\\', n));
+                }
+            }
+            // Windows path separator
+            _ => self.word.extend(std::iter::repeat_n('\\', n)),
+        }
+    }
+
+    fn run(mut self) -> Vec<Token> {
+        while let Some(c) = self.chars.next() {
+            match c {
+                ' ' | '\t' | '\r' => self.end_word(),
+                '\n' => self.end_line(),
+                '\\' => self.backslashes(),
+                '$' => {
+                    self.chars.next_if_eq(&'$');
+                    self.word.push('$');
+                }
+                '#' => while self.chars.next_if(|v| *v != '\n').is_some() {},
+                // Colons in Windows drive letters are not followed by a separator
+                ':' if !self.seen_colon && Self::is_separator(self.chars.peek()) => {
+                    self.end_word();
+                    self.tokens.push(Token::Colon);
+                    self.seen_colon = true;
+                }
+                c => self.word.push(c),
+            }
+        }
+        self.end_line();
+        self.tokens
+    }
+}
+
+/// Parses a Make depfile.
+///
+/// Handles line continuations, escaped spaces (`\ `), `\#`, `$$`, several targets per rule and
+/// several rules per file.
+pub fn parse_depfile(input: &str) -> Result<Depfile> {
+    let mut depfile = Depfile::default();
+    let mut rule = Rule::default();
+    let mut seen_colon = false;
+    let mut line = 1;
+    for token in Lexer::new(input).run() {
+        match token {
+            Token::Word(word) if seen_colon => rule.prerequisites.push(PathBuf::from(word)),
+            Token::Word(word) => rule.targets.push(PathBuf::from(word)),
+            Token::Colon => seen_colon = true,
+            Token::Newline => {
+                if !seen_colon && !rule.targets.is_empty() {
+                    return Err(Error::DepfileFormatError(line));
+                }
+                if seen_colon {
+                    depfile.rules.push(std::mem::take(&mut rule));
+                }
+                seen_colon = false;
+                line += 1;
+            }
+        }
+    }
+    Ok(depfile)
+}
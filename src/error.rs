@@ -0,0 +1,104 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatusError;
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(io::Error),
+    ExitStatusError(ExitStatusError),
+    ShellWordsParseError(shell_words::ParseError),
+    RegexError(regex::Error),
+    SerdeJsonError(serde_json::Error),
+    CommandFormatError,
+    /// Entry of compilation database lacks what is required
+    EntryFormatError(&'static str),
+    /// Invalid entry of compilation database at the given index
+    InvalidEntryError(usize, Box<Error>),
+    /// The compiler cannot do what is asked
+    UnsupportedError(&'static str),
+    /// Malformed depfile at the given logical line
+    DepfileFormatError(usize),
+    DepfileNotFoundError,
+    /// Neither `compile_commands.json` nor `compile_flags.txt` is in the directory
+    DatabaseNotFoundError(PathBuf),
+    /// Malformed `.ninja_deps`
+    NinjaDepsFormatError(&'static str),
+    /// Output of the compile command is not in `.ninja_deps`
+    NinjaDepsNotFoundError,
+    /// Output of `-E -v` has no `#include <...>` search list
+    SearchListNotFoundError,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IoError(error) => write!(f, "I/O error: {}", error),
+            Error::ExitStatusError(error) => write!(f, "Compiler failed: {}", error),
+            Error::ShellWordsParseError(error) => write!(f, "Failed to split command: {}", error),
+            Error::RegexError(error) => write!(f, "Regex error: {}", error),
+            Error::SerdeJsonError(error) => write!(f, "JSON error: {}", error),
+            Error::CommandFormatError => {
+                write!(f, "Neither `command` nor `arguments` is given")
+            }
+            Error::EntryFormatError(why) => write!(f, "{}", why),
+            Error::InvalidEntryError(index, why) => {
+                write!(f, "Invalid entry at index {}: {}", index, why)
+            }
+            Error::UnsupportedError(what) => write!(f, "Unsupported: {}", what),
+            Error::DepfileFormatError(line) => {
+                write!(f, "Malformed depfile: missing `:` at line {}", line)
+            }
+            Error::DepfileNotFoundError => write!(f, "No depfile found in the build tree"),
+            Error::DatabaseNotFoundError(directory) => write!(
+                f,
+                "No compile_commands.json or compile_flags.txt in {}",
+                directory.display()
+            ),
+            Error::NinjaDepsFormatError(why) => write!(f, "Malformed .ninja_deps: {}", why),
+            Error::NinjaDepsNotFoundError => {
+                write!(f, "Output not recorded in .ninja_deps")
+            }
+            Error::SearchListNotFoundError => {
+                write!(
+                    f,
+                    "No #include <...> search list in the output of the compiler"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::IoError(error)
+    }
+}
+
+impl From<ExitStatusError> for Error {
+    fn from(error: ExitStatusError) -> Self {
+        Error::ExitStatusError(error)
+    }
+}
+
+impl From<shell_words::ParseError> for Error {
+    fn from(error: shell_words::ParseError) -> Self {
+        Error::ShellWordsParseError(error)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(error: regex::Error) -> Self {
+        Error::RegexError(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::SerdeJsonError(error)
+    }
+}
@@ -0,0 +1,144 @@
+//! Graphviz DOT export of translation unit to dependency graph.
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::dependency::{DependencyKind, TranslationUnit};
+
+const SYSTEM_NODE: &str = "<system headers>";
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GraphOptions {
+    /// Put nodes in the same directory into a cluster.
+    pub cluster_by_directory: bool,
+    /// Merge all system headers into a single node.
+    pub collapse_system_headers: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NodeKind {
+    TranslationUnit,
+    Dependency,
+    System,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_node<W: Write>(
+    w: &mut W,
+    indent: &str,
+    id: &str,
+    label: &str,
+    kind: NodeKind,
+) -> io::Result<()> {
+    let shape = match kind {
+        NodeKind::TranslationUnit => "box",
+        NodeKind::Dependency => "ellipse",
+        NodeKind::System => "folder",
+    };
+    writeln!(
+        w,
+        "{}\"{}\" [label=\"{}\", shape={}];",
+        indent,
+        escape(id),
+        escape(label),
+        shape
+    )
+}
+
+/// Writes the graph of `units` in DOT language.
+///
+/// Dependencies rejected by `filter` are omitted. A translation unit does not depend on itself.
+pub fn write_dot<W, F>(
+    w: &mut W,
+    units: &[TranslationUnit],
+    options: GraphOptions,
+    filter: F,
+) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(&Path, DependencyKind) -> bool,
+{
+    let mut nodes: BTreeMap<PathBuf, NodeKind> = BTreeMap::new();
+    let mut edges: BTreeSet<(PathBuf, PathBuf)> = BTreeSet::new();
+    for unit in units.iter().filter(|v| v.error.is_none()) {
+        let source = unit
+            .source()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| unit.directory.join(&unit.file));
+        nodes.insert(source.clone(), NodeKind::TranslationUnit);
+        for path in unit.dependencies.iter() {
+            if path == &source {
+                continue;
+            }
+            let kind = unit.classify(path);
+            if !filter(path, kind) {
+                continue;
+            }
+            let node = if options.collapse_system_headers && kind == DependencyKind::System {
+                nodes.insert(PathBuf::from(SYSTEM_NODE), NodeKind::System);
+                PathBuf::from(SYSTEM_NODE)
+            } else {
+                nodes.entry(path.clone()).or_insert(NodeKind::Dependency);
+                path.clone()
+            };
+            edges.insert((source.clone(), node));
+        }
+    }
+
+    writeln!(w, "digraph dependency {{")?;
+    writeln!(w, "    rankdir=LR;")?;
+    if options.cluster_by_directory {
+        let mut clusters: BTreeMap<&Path, Vec<(&PathBuf, NodeKind)>> = BTreeMap::new();
+        for (path, kind) in nodes.iter() {
+            let directory = match kind {
+                NodeKind::System => Path::new(""),
+                _ => path.parent().unwrap_or_else(|| Path::new("")),
+            };
+            clusters.entry(directory).or_default().push((path, *kind));
+        }
+        for (i, (directory, nodes)) in clusters.iter().enumerate() {
+            let indent = if directory.as_os_str().is_empty() {
+                "    "
+            } else {
+                writeln!(w, "    subgraph \"cluster_{}\" {{", i)?;
+                writeln!(
+                    w,
+                    "        label=\"{}\";",
+                    escape(&directory.display().to_string())
+                )?;
+                "        "
+            };
+            for (path, kind) in nodes {
+                let label = match kind {
+                    NodeKind::System => SYSTEM_NODE.to_string(),
+                    _ => path
+                        .file_name()
+                        .map(|v| v.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.display().to_string()),
+                };
+                write_node(w, indent, &path.display().to_string(), &label, *kind)?;
+            }
+            if !directory.as_os_str().is_empty() {
+                writeln!(w, "    }}")?;
+            }
+        }
+    } else {
+        for (path, kind) in nodes.iter() {
+            let id = path.display().to_string();
+            write_node(w, "    ", &id, &id, *kind)?;
+        }
+    }
+    for (from, to) in edges.iter() {
+        writeln!(
+            w,
+            "    \"{}\" -> \"{}\";",
+            escape(&from.display().to_string()),
+            escape(&to.display().to_string())
+        )?;
+    }
+    writeln!(w, "}}")
+}
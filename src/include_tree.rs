@@ -0,0 +1,183 @@
+use log::{error, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::compile_command::CompileCommand;
+use crate::dependency::{rewrite_arguments, run_compiler, TranslationUnit};
+use crate::error::Result;
+
+/// A header and headers it includes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Include {
+    /// Canonicalized if exists.
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<Include>,
+}
+
+impl Include {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            includes: Vec::new(),
+        }
+    }
+
+    /// Iterates over this node and all of its descendants in pre-order.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &Include> + '_> {
+        Box::new(std::iter::once(self).chain(self.includes.iter().flat_map(Include::iter)))
+    }
+}
+
+fn find_chains<'a>(
+    includes: &'a [Include],
+    path: &Path,
+    chain: &mut Vec<&'a Path>,
+    result: &mut Vec<Vec<&'a Path>>,
+) {
+    for include in includes {
+        chain.push(&include.path);
+        if include.path == path {
+            result.push(chain.clone());
+        }
+        find_chains(&include.includes, path, chain, result);
+        chain.pop();
+    }
+}
+
+/// Returns all include chains that reach `path`, shortest first.
+///
+/// Each chain starts at a header included by the main source file and ends with `path`.
+pub fn include_chains<'a>(includes: &'a [Include], path: &Path) -> Vec<Vec<&'a Path>> {
+    let mut result = Vec::new();
+    find_chains(includes, path, &mut Vec::new(), &mut result);
+    result.sort_by_key(Vec::len);
+    result
+}
+
+/// What [`describe_dependents`] prints for each translation unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependentField {
+    /// The source file as written in the compile command.
+    File,
+    /// The output of the compile command. Translation units without it are skipped.
+    Output,
+    /// The whole command line.
+    Command,
+}
+
+/// Returns compile commands with their translation units that depend on any of `paths`.
+///
+/// `paths` are canonicalized if exist, so that relative paths match canonical dependencies.
+pub fn dependents<'a>(
+    paths: &[PathBuf],
+    compile_commands: &'a [CompileCommand],
+    translation_units: &'a [TranslationUnit],
+) -> Vec<(&'a CompileCommand, &'a TranslationUnit)> {
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|v| v.canonicalize().unwrap_or_else(|_| v.clone()))
+        .collect();
+    compile_commands
+        .iter()
+        .zip(translation_units)
+        .filter(|(_, unit)| unit.dependencies.iter().any(|v| paths.contains(v)))
+        .collect()
+}
+
+/// Returns `field` of each of `dependents`, sorted and without duplicates.
+pub fn describe_dependents(
+    dependents: &[(&CompileCommand, &TranslationUnit)],
+    field: DependentField,
+) -> Vec<String> {
+    let mut result = Vec::new();
+    for (command, unit) in dependents {
+        let line = match field {
+            DependentField::File => unit.file.display().to_string(),
+            DependentField::Output => match unit.output {
+                Some(ref output) => output.display().to_string(),
+                None => {
+                    warn!("No output for file: {:?}", unit.file);
+                    continue;
+                }
+            },
+            DependentField::Command => match command.command_line() {
+                Ok(command_line) => command_line,
+                Err(why) => {
+                    error!("{}: {}", unit.file.display(), why);
+                    continue;
+                }
+            },
+        };
+        result.push(line);
+    }
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Inserts `include` as the last node at `depth`, where 1 is a header included by the source.
+pub(crate) fn insert_include(includes: &mut Vec<Include>, depth: usize, include: Include) {
+    match includes.last_mut() {
+        Some(parent) if depth > 1 => insert_include(&mut parent.includes, depth - 1, include),
+        _ => includes.push(include),
+    }
+}
+
+/// Returns the source file of `command` and all headers in `includes` without duplicates.
+pub(crate) fn flatten_includes(command: &CompileCommand, includes: &[Include]) -> Vec<PathBuf> {
+    let mut dependencies = Vec::new();
+    if let Ok(source) = command.path().canonicalize() {
+        dependencies.push(source);
+    }
+    for include in includes.iter().flat_map(Include::iter) {
+        if !dependencies.contains(&include.path) {
+            dependencies.push(include.path.clone());
+        }
+    }
+    dependencies
+}
+
+/// Parses the trace of `-H` and returns the include tree of the main source file.
+///
+/// Lines other than the trace, such as diagnostics, are written back to stderr.
+/// Relative paths are resolved against `directory`.
+pub fn parse_include_trace<R: BufRead>(trace: R, directory: &Path) -> Result<Vec<Include>> {
+    let mut result = Vec::new();
+    let mut stderr = io::stderr().lock();
+    let mut guard_hints = false;
+    for line in trace.lines() {
+        let line = line?;
+        let mut depth = line.bytes().take_while(|c| *c == b'.').count();
+        // `!` and `x` mark valid and invalid precompiled headers
+        if depth == 0 && (line.starts_with("! ") || line.starts_with("x ")) {
+            depth = 1;
+        }
+        if depth == 0 || line.as_bytes().get(depth) != Some(&b' ') {
+            if line.starts_with("Multiple include guards may be useful for:") {
+                guard_hints = true;
+            }
+            if !guard_hints {
+                writeln!(stderr, "{}", line)?;
+            }
+            continue;
+        }
+        let path = directory.join(&line[depth + 1..]);
+        let path = path.canonicalize().unwrap_or(path);
+        trace!("parse_include_trace: depth={}, path={:?}", depth, path);
+        insert_include(&mut result, depth, Include::new(path));
+    }
+    Ok(result)
+}
+
+/// Runs the gcc-style compile command with `-H -fsyntax-only` and returns its include tree.
+pub fn dump_include_tree(command: &CompileCommand) -> Result<Vec<Include>> {
+    let args = rewrite_arguments(command, &["-H", "-fsyntax-only"])?;
+    let output = run_compiler(command, &args)?;
+    let includes = parse_include_trace(output.stderr.as_slice(), &command.directory)?;
+    output.status.exit_ok()?;
+    Ok(includes)
+}
@@ -0,0 +1,68 @@
+//! Extracts source code dependencies of each translation unit in `compile_commands.json`.
+//!
+//! ```no_run
+//! use dump_dependency::{dump_dependencies, load_compile_commands, DumpOptions};
+//! use std::path::Path;
+//!
+//! let entries = load_compile_commands(Path::new("compile_commands.json")).unwrap();
+//! let commands: Vec<_> = entries.into_iter().filter_map(Result::ok).collect();
+//! for unit in dump_dependencies(&commands, &DumpOptions::default()) {
+//!     println!("{}: {:?}", unit.file.display(), unit.dependencies);
+//! }
+//! ```
+#![feature(exit_status_error)]
+
+pub mod arguments;
+mod build_depfile;
+mod build_log;
+mod compile_command;
+mod compiler;
+mod database;
+mod dependency;
+mod depfile;
+mod error;
+pub mod graph;
+mod include_tree;
+mod msvc;
+mod ninja_deps;
+mod p1689;
+pub mod report;
+mod scan_deps;
+mod scanner;
+mod system_header;
+mod variant;
+
+pub use build_depfile::{depfile_candidates, read_build_depfile, BuildDepfile};
+pub use build_log::parse_build_log;
+pub use compile_command::{
+    for_each_compile_command, load_compile_commands, parse_compile_commands, read_compile_commands,
+    CompileCommand, CompileCommandStream,
+};
+pub use compiler::{Compiler, CompilerFamily, Gcc};
+pub use database::{
+    discover_databases, find_database, load_database, merge_databases, parse_compile_flags,
+    stream_database, Conflict, COMPILE_COMMANDS_FILE_NAME, COMPILE_FLAGS_FILE_NAME,
+};
+pub use dependency::{
+    dump_dependencies, dump_dependencies_streaming, dump_dependency, dump_user_dependency,
+    parse_dependency, Backend, DependencyKind, DumpOptions, TranslationUnit,
+};
+pub use depfile::{parse_depfile, Depfile, Rule};
+pub use error::{Error, Result};
+pub use include_tree::{
+    dependents, describe_dependents, dump_include_tree, include_chains, parse_include_trace,
+    DependentField, Include,
+};
+pub use msvc::{parse_show_includes, Msvc};
+pub use ninja_deps::{
+    ninja_dependencies, parse_ninja_deps, read_ninja_deps, NinjaDeps, NinjaDepsRecord,
+    NINJA_DEPS_FILE_NAME,
+};
+pub use p1689::{
+    dump_modules, parse_p1689, LookupMethod, ModuleDependencies, P1689Rule, ProvidedModule,
+    RequiredModule, P1689,
+};
+pub use scan_deps::scan_dependencies;
+pub use scanner::{ScanMode, Scanner};
+pub use system_header::{parse_search_list, SystemDirectories, FALLBACK_SYSTEM_DIRECTORIES};
+pub use variant::{label_variants, merge_variants, VariantMode};
@@ -1,227 +1,890 @@
-#![feature(exit_status_error)]
-
-use clap::{Parser, Subcommand};
+use clap::{ArgEnum, Parser, Subcommand};
+use dump_dependency::graph::{write_dot, GraphOptions};
+use dump_dependency::report::{
+    ChainReport, DiscoveryReport, ModuleReport, ProjectReport, Report, SharedHeaderReport,
+    TranslationUnitReport,
+};
+use dump_dependency::{
+    dependents, describe_dependents, discover_databases, dump_dependencies,
+    dump_dependencies_streaming, include_chains, label_variants, load_database, merge_databases,
+    merge_variants, parse_build_log, stream_database, Backend, CompileCommand, DependencyKind,
+    DependentField, DumpOptions, Error, Include, ScanMode, TranslationUnit, VariantMode,
+};
 use log::error;
 #[allow(unused_imports)]
 use log::{info, trace, warn};
-#[allow(unused_imports)]
-use rayon::prelude::*;
-use regex::Regex;
-use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashSet};
 use std::env;
 use std::ffi::OsStr;
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::io;
-use std::io::BufReader;
-use std::io::Cursor;
-use std::io::Write;
-#[allow(unused_imports)]
-use std::io::{BufRead, Read};
-use std::path::Path;
-use std::path::PathBuf;
-use std::process::Command;
-use std::process::ExitStatusError;
+use std::path::{Path, PathBuf};
+use std::process;
 
 #[derive(Parser)]
-#[clap(author, version, about, long_about = None)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    subcommand_precedence_over_arg = true
+)]
 struct Cli {
-    compile_commands: String,
+    #[clap(
+        required_unless_present_any = &["from-build-log", "discover"],
+        help = "compile_commands.json, compile_flags.txt, build directory that has either of them, or - for stdin. Several databases are merged"
+    )]
+    compile_commands: Vec<String>,
+    #[clap(
+        long = "from-build-log",
+        conflicts_with = "compile-commands",
+        help = "Recover compile commands from this build log, e.g. output of `make -nkw` or `ninja -v`, instead of compile_commands.json. Commands run in the current directory unless the log says otherwise"
+    )]
+    from_build_log: Option<PathBuf>,
+    #[clap(
+        long = "discover",
+        conflicts_with_all = &["compile-commands", "from-build-log"],
+        help = "Find every compile_commands.json under this directory and report each project separately"
+    )]
+    discover: Option<PathBuf>,
+    #[clap(
+        long = "write-compile-commands",
+        help = "Write the compile commands in use to this file as compile_commands.json"
+    )]
+    write_compile_commands: Option<PathBuf>,
     #[clap(
         long = "exclude-system-headers",
         help = "Exclude system headers from dependency list"
     )]
     exclude_system_headers: bool,
+    #[clap(
+        long = "system-prefix",
+        help = "Regard files under this directory as system headers. Can be given multiple times"
+    )]
+    system_prefixes: Vec<PathBuf>,
+    #[clap(
+        long = "launcher",
+        help = "Skip this compiler launcher in addition to ccache, sccache, distcc, icecc and buildcache. Can be given multiple times"
+    )]
+    launchers: Vec<String>,
+    #[clap(
+        long = "msvc-include-prefix",
+        help = "Prefix of /showIncludes lines printed by localized cl [default: \"Note: including file:\"]"
+    )]
+    msvc_include_prefix: Option<String>,
+    #[clap(
+        long = "clang-scan-deps",
+        help = "Scan all translation units at once with this clang-scan-deps, falling back to the compiler for failed ones"
+    )]
+    clang_scan_deps: Option<PathBuf>,
+    #[clap(
+        long = "scanner",
+        arg_enum,
+        conflicts_with = "clang-scan-deps",
+        help = "Scan sources and headers with the built-in scanner instead of running compilers"
+    )]
+    scanner: Option<ScannerMode>,
+    #[clap(
+        long = "from-depfiles",
+        conflicts_with_all = &["clang-scan-deps", "scanner"],
+        help = "Read depfiles the build left in the build tree instead of running compilers"
+    )]
+    from_depfiles: bool,
+    #[clap(
+        long = "depfile-pattern",
+        help = "Where to find depfiles with --from-depfiles in addition to -MF and <output>.d, e.g. \"deps/{stem}.d\". {output}, {file} and {stem} are replaced"
+    )]
+    depfile_pattern: Option<String>,
+    #[clap(
+        long = "from-ninja-deps",
+        conflicts_with_all = &["clang-scan-deps", "scanner", "from-depfiles"],
+        help = "Read .ninja_deps of a finished Ninja build instead of running compilers"
+    )]
+    from_ninja_deps: bool,
+    #[clap(
+        long = "ninja-deps",
+        help = "Use this .ninja_deps with --from-ninja-deps instead of the one in the directory of each compile command"
+    )]
+    ninja_deps: Option<PathBuf>,
     #[clap(long = "headers", help = "List only headers")]
     headers: bool,
+    #[clap(
+        long = "format",
+        arg_enum,
+        default_value = "text",
+        help = "Output format"
+    )]
+    format: Format,
+    #[clap(
+        long = "include-tree",
+        help = "Extract nested include tree with `-H` instead of `-M`"
+    )]
+    include_tree: bool,
+    #[clap(
+        long = "modules",
+        help = "Also ask the compiler for C++20 module dependencies in P1689 format"
+    )]
+    modules: bool,
+    #[clap(
+        long = "variants",
+        arg_enum,
+        default_value = "union",
+        help = "How to report files compiled by more than one command. rdeps always reports each"
+    )]
+    variants: Variants,
     #[clap(subcommand)]
     command: CliSubCommand,
 }
 
 #[derive(Subcommand)]
 enum CliSubCommand {
+    /// List dependencies of all translation units
     List,
+    /// List dependencies of each translation unit
+    Deps,
+    /// List translation units that depend on any of the given paths
+    Rdeps {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+        #[clap(
+            long = "print",
+            arg_enum,
+            default_value = "file",
+            help = "What to print for each translation unit"
+        )]
+        print: RdepsPrint,
+    },
+    /// Print include tree of each translation unit. Implies --include-tree
+    Tree,
+    /// Print include chains that bring a header into translation units. Implies --include-tree
+    Why {
+        header: PathBuf,
+        #[clap(long = "from", help = "Look into this translation unit only")]
+        from: Option<PathBuf>,
+        #[clap(long = "all", help = "Print all chains instead of the shortest one")]
+        all: bool,
+    },
+    /// List C++20 modules with translation units that provide and import them. Implies --modules
+    Modules,
+    /// List headers that translation units of more than one project depend on. Projects are the
+    /// databases given or found by --discover
+    Shared,
+    /// Print dependency graph in Graphviz DOT language
+    Graph {
+        #[clap(
+            long = "cluster-by-directory",
+            help = "Group nodes in the same directory"
+        )]
+        cluster_by_directory: bool,
+        #[clap(
+            long = "collapse-system-headers",
+            help = "Merge system headers into a single node"
+        )]
+        collapse_system_headers: bool,
+    },
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum RdepsPrint {
+    File,
+    Output,
+    Command,
 }
 
-#[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-struct CompileCommand {
-    directory: PathBuf,
-    #[serde(default)]
-    command: Option<String>,
-    #[serde(default)]
-    arguments: Option<Vec<String>>,
-    file: PathBuf,
+#[derive(ArgEnum, Clone, Copy)]
+enum ScannerMode {
+    /// Follow every `#include` ignoring `#if`
+    Superset,
+    /// Evaluate simple `#if` with `-D` flags
+    Evaluate,
 }
 
-#[derive(Debug)]
-enum Error {
-    IoError(io::Error),
-    ExitStatusError(ExitStatusError),
-    ShellWordsParseError(shell_words::ParseError),
-    RegexError(regex::Error),
-    CommandFormatError,
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+enum Variants {
+    /// Dependencies of any command
+    Union,
+    /// Dependencies common to all commands
+    Intersection,
+    /// Each command separately
+    Each,
 }
 
-type Result<T> = std::result::Result<T, Error>;
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+    Ndjson,
+}
 
-impl From<io::Error> for Error {
-    fn from(error: io::Error) -> Self {
-        Error::IoError(error)
+impl Cli {
+    fn is_listed(&self, path: &Path, kind: DependencyKind) -> bool {
+        if self.exclude_system_headers && kind == DependencyKind::System {
+            return false;
+        }
+        if self.headers {
+            if let Some(ext) = path.extension().and_then(OsStr::to_str) {
+                if !ext.starts_with('h') {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn report(&self, unit: &TranslationUnit) -> TranslationUnitReport {
+        TranslationUnitReport::new(unit, |path, kind| self.is_listed(path, kind))
     }
 }
 
-impl From<ExitStatusError> for Error {
-    fn from(error: ExitStatusError) -> Self {
-        Error::ExitStatusError(error)
+fn print_report(format: Format, report: Report) {
+    match format {
+        Format::Text => unreachable!(),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Failed to serialize")
+        ),
+        Format::Ndjson => {
+            for record in report.into_records() {
+                println!(
+                    "{}",
+                    serde_json::to_string(&record).expect("Failed to serialize")
+                );
+            }
+        }
     }
 }
 
-impl From<shell_words::ParseError> for Error {
-    fn from(error: shell_words::ParseError) -> Self {
-        Error::ShellWordsParseError(error)
+fn print_discovery_report(format: Format, report: DiscoveryReport) {
+    match format {
+        Format::Text => unreachable!(),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Failed to serialize")
+        ),
+        Format::Ndjson => {
+            for record in report.into_records() {
+                println!(
+                    "{}",
+                    serde_json::to_string(&record).expect("Failed to serialize")
+                );
+            }
+        }
     }
 }
 
-impl From<regex::Error> for Error {
-    fn from(error: regex::Error) -> Self {
-        Error::RegexError(error)
+fn print_file(file: &Path, variant: Option<&str>) {
+    match variant {
+        Some(variant) => println!("{} [{}]:", file.display(), variant),
+        None => println!("{}:", file.display()),
     }
 }
 
-fn parse_dependency<R: Read>(output: BufReader<R>) -> Result<Vec<PathBuf>> {
-    let re = Regex::new(r"\s*(.*) \\")?;
-    let mut result = Vec::new();
-    for line in output.lines() {
-        if let Some(matches) = re.captures(&line?) {
-            if let Some(path) = matches.get(1) {
-                let path = Path::new(path.as_str());
-                if path.exists() {
-                    result.push(path.canonicalize()?);
-                }
+fn list(args: &Cli, translation_units: Vec<TranslationUnit>) -> Option<Report> {
+    let reports: Vec<_> = translation_units.iter().map(|v| args.report(v)).collect();
+
+    let mut done_list = HashSet::new();
+    let mut dependency_list = Vec::new();
+    for report in reports.iter() {
+        for v in report.dependencies.iter() {
+            if done_list.insert(&v.path) {
+                dependency_list.push(v.clone());
             }
         }
     }
-    if result.is_empty() {
-        warn!("No dependency found");
+    dependency_list.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if args.format != Format::Text {
+        let mut report = Report::new(reports);
+        report.dependencies = Some(dependency_list);
+        return Some(report);
+    }
+    for v in dependency_list {
+        println!("{}", v.path.display());
     }
-    return Ok(result);
+    None
 }
 
-fn dump_dependency(command: &CompileCommand) -> Result<Vec<PathBuf>> {
-    let mut args = if let Some(ref arguments) = command.arguments {
-        arguments.clone()
-    } else if let Some(ref command) = command.command {
-        shell_words::split(command)?
-    } else {
-        return Err(Error::CommandFormatError);
-    };
-    assert_ne!(args.len(), 0);
-    trace!("dump_dependency: args={:?}", args);
-
-    #[derive(Debug)]
-    struct ReplaceTargetOption {
-        o: Option<usize>,
-    }
-    let replace_target_option: ReplaceTargetOption = (|args: &Vec<String>| -> ReplaceTargetOption {
-        let o = args.iter().position(|v| v == &String::from("-o"));
-        ReplaceTargetOption { o }
-    })(&args);
-    trace!(
-        "dump_dependency: replace_target_option={:?}",
-        replace_target_option
-    );
-    if let Some(o) = replace_target_option.o {
-        args.remove(o + 1);
-        args.remove(o);
-    }
+fn deps(args: &Cli, mut translation_units: Vec<TranslationUnit>) -> Option<Report> {
+    translation_units.sort_by(|a, b| a.file.cmp(&b.file));
+    let reports: Vec<_> = translation_units.iter().map(|v| args.report(v)).collect();
 
-    args.insert(1, String::from("-M"));
+    if args.format != Format::Text {
+        return Some(Report::new(reports));
+    }
+    for report in reports {
+        if report.error.is_some() {
+            continue;
+        }
+        print_file(&report.file, report.variant.as_deref());
+        let mut dependencies: Vec<_> = report.dependencies.iter().map(|v| &v.path).collect();
+        dependencies.sort();
+        for path in dependencies {
+            println!("  {}", path.display());
+        }
+    }
+    None
+}
 
-    let output = Command::new(&args[0])
-        .args(&args[1..])
-        .current_dir(&command.directory)
-        .output()?;
-    if !output.stderr.is_empty() {
-        // Tell human that a error occured
-        let mut stdout = io::stdout().lock();
-        stdout.write_all(&output.stderr)?;
+fn rdeps(
+    args: &Cli,
+    paths: &[PathBuf],
+    print: RdepsPrint,
+    compile_commands: &[CompileCommand],
+    translation_units: Vec<TranslationUnit>,
+) -> Option<Report> {
+    let matched = dependents(paths, compile_commands, &translation_units);
+    if args.format != Format::Text {
+        let reports = matched.iter().map(|(_, unit)| args.report(unit)).collect();
+        return Some(Report::new(reports));
+    }
+    let field = match print {
+        RdepsPrint::File => DependentField::File,
+        RdepsPrint::Output => DependentField::Output,
+        RdepsPrint::Command => DependentField::Command,
+    };
+    let result = describe_dependents(&matched, field);
+    for line in result {
+        println!("{}", line);
     }
-    output.status.exit_ok()?;
-    assert_ne!(output.stdout.len(), 0);
+    None
+}
 
-    Ok(parse_dependency(BufReader::new(Cursor::new(
-        output.stdout,
-    )))?)
+fn print_include_tree(args: &Cli, unit: &TranslationUnit, includes: &[Include], depth: usize) {
+    for include in includes {
+        if !args.is_listed(&include.path, unit.classify(&include.path)) {
+            continue;
+        }
+        println!("{:width$}{}", "", include.path.display(), width = depth * 2);
+        print_include_tree(args, unit, &include.includes, depth + 1);
+    }
 }
 
-fn main() {
-    env_logger::init();
+fn tree(args: &Cli, mut translation_units: Vec<TranslationUnit>) -> Option<Report> {
+    translation_units.sort_by(|a, b| a.file.cmp(&b.file));
+    if args.format != Format::Text {
+        let reports = translation_units.iter().map(|v| args.report(v)).collect();
+        return Some(Report::new(reports));
+    }
+    for unit in translation_units.iter() {
+        if let Some(ref includes) = unit.includes {
+            print_file(&unit.file, unit.variant.as_deref());
+            print_include_tree(args, unit, includes, 1);
+        }
+    }
+    None
+}
 
-    let args = Cli::parse();
-    info!("args = {:?}", env::args());
+fn why(
+    args: &Cli,
+    header: &Path,
+    from: Option<&Path>,
+    all: bool,
+    mut translation_units: Vec<TranslationUnit>,
+) -> Option<Report> {
+    let header = header
+        .canonicalize()
+        .unwrap_or_else(|_| header.to_path_buf());
+    let from = from.map(|v| v.canonicalize().unwrap_or_else(|_| v.to_path_buf()));
+    translation_units.sort_by(|a, b| a.file.cmp(&b.file));
 
-    let compile_commands = fs::read_to_string(&args.compile_commands)
-        .expect(format!("Failed to open file: {:?}", &args.compile_commands).as_str());
-    let compile_commands: Vec<CompileCommand> =
-        serde_json::from_str(&compile_commands).expect("Failed to parse");
-    assert!(compile_commands.len() > 0);
-
-    // Filter out commands for same file
-    let compile_commands = {
-        let mut unduplicated_compile_commands = Vec::new();
-        let mut done_list = HashSet::new();
-        for command in compile_commands.iter() {
-            if done_list.contains(&command.file) {
-                warn!(
-                    "Another command for same file. Skip: file={:?}, arguments={:?}, command={:?}",
-                    command.file, command.arguments, command.command
-                );
+    let mut reports = Vec::new();
+    let mut chains = Vec::new();
+    for unit in translation_units.iter() {
+        if let Some(ref from) = from {
+            if unit.source() != Some(from.as_path()) && &unit.file != from {
                 continue;
             }
-            done_list.insert(&command.file);
-            unduplicated_compile_commands.push(command);
         }
-        unduplicated_compile_commands
-    };
+        let includes = match unit.includes {
+            Some(ref includes) => includes,
+            None => continue,
+        };
+        let mut found = include_chains(includes, &header);
+        if found.is_empty() {
+            continue;
+        }
+        if !all {
+            found.truncate(1);
+        }
+        reports.push(args.report(unit));
+        for chain in found {
+            chains.push(ChainReport {
+                file: unit.file.clone(),
+                chain: chain.into_iter().map(Path::to_path_buf).collect(),
+            });
+        }
+    }
 
-    let dependencies: Vec<_> = compile_commands
-        .par_iter()
-        .map(|command| {
-            trace!("file={:?}", command.file);
+    if args.format != Format::Text {
+        let mut report = Report::new(reports);
+        report.chains = Some(chains);
+        return Some(report);
+    }
+    for chain in chains {
+        print!("{}", chain.file.display());
+        for path in chain.chain {
+            print!(" -> {}", path.display());
+        }
+        println!();
+    }
+    None
+}
 
-            dump_dependency(command)
-        })
+fn modules(args: &Cli, translation_units: Vec<TranslationUnit>) -> Option<Report> {
+    let modules = ModuleReport::collect(&translation_units);
+    if args.format != Format::Text {
+        let reports = translation_units.iter().map(|v| args.report(v)).collect();
+        let mut report = Report::new(reports);
+        report.modules = Some(modules);
+        return Some(report);
+    }
+    for module in modules {
+        println!("{}:", module.name);
+        for file in module.provided_by.iter() {
+            println!("  provided by {}", file.display());
+        }
+        for file in module.imported_by.iter() {
+            println!("  imported by {}", file.display());
+        }
+    }
+    None
+}
+
+fn shared(args: &Cli, projects: Vec<(PathBuf, Vec<TranslationUnit>)>) {
+    if projects.len() < 2 {
+        warn!("shared needs several databases or --discover");
+    }
+    let units: Vec<_> = projects
+        .iter()
+        .map(|(database, units)| (database.clone(), units.as_slice()))
         .collect();
+    let headers = SharedHeaderReport::collect(&units, |path, kind| args.is_listed(path, kind));
 
-    let mut dependency_list = HashSet::new();
-    for dependency in dependencies {
-        match dependency {
-            Ok(ref paths) => {
-                for v in paths.iter().cloned() {
-                    if args.exclude_system_headers {
-                        if v.starts_with("/usr") {
-                            continue;
-                        }
-                    }
-                    if args.headers {
-                        if let Some(ext) = v.extension().and_then(OsStr::to_str) {
-                            if !ext.starts_with("h") {
-                                continue;
-                            }
-                        }
-                    }
-                    dependency_list.insert(v);
-                }
+    if args.format != Format::Text {
+        let projects = projects
+            .iter()
+            .map(|(database, units)| ProjectReport {
+                database: database.clone(),
+                report: Report::new(units.iter().map(|v| args.report(v)).collect()),
+            })
+            .collect();
+        let mut report = DiscoveryReport::new(projects);
+        report.shared_headers = Some(headers);
+        return print_discovery_report(args.format, report);
+    }
+    for header in headers {
+        println!("{}", header.path.display());
+        for database in header.projects {
+            println!("  {}", database.display());
+        }
+    }
+}
+
+fn graph(args: &Cli, options: GraphOptions, translation_units: Vec<TranslationUnit>) {
+    if args.format != Format::Text {
+        warn!("graph supports DOT output only. Ignore --format");
+    }
+    let mut stdout = io::stdout().lock();
+    write_dot(&mut stdout, &translation_units, options, |path, kind| {
+        args.is_listed(path, kind)
+    })
+    .expect("Failed to write graph");
+}
+
+/// Loads the database at `path`, logging invalid entries. `None` if the database is unreadable.
+fn load_entries(path: &str) -> Option<Vec<CompileCommand>> {
+    let entries = match load_database(Path::new(path)) {
+        Ok(entries) => entries,
+        Err(why) => {
+            error!("{}: {}", path, why);
+            return None;
+        }
+    };
+    let mut compile_commands = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(command) => compile_commands.push(command),
+            Err(why) => error!("{}: {}", path, why),
+        }
+    }
+    info!("{}: {} compile commands", path, compile_commands.len());
+    Some(compile_commands)
+}
+
+fn load_databases(paths: &[String]) -> Vec<CompileCommand> {
+    let mut databases = Vec::new();
+    for path in paths {
+        match load_entries(path) {
+            Some(compile_commands) => databases.push(compile_commands),
+            None => process::exit(1),
+        }
+    }
+
+    let (compile_commands, conflicts) = merge_databases(databases);
+    for conflict in conflicts {
+        let databases: Vec<_> = conflict.databases.iter().map(|i| &paths[*i]).collect();
+        warn!(
+            "{}: compiled differently in {:?}. All commands are kept",
+            conflict.file.display(),
+            databases
+        );
+    }
+    if compile_commands.is_empty() {
+        error!("{}: No valid compile command", paths.join(", "));
+        process::exit(1);
+    }
+    compile_commands
+}
+
+fn load_build_log(path: &Path) -> Vec<CompileCommand> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(why) => {
+            error!("{}: {}", path.display(), why);
+            process::exit(1);
+        }
+    };
+    let directory = env::current_dir().expect("Failed to get current directory");
+    let compile_commands = parse_build_log(&content, &directory);
+    info!(
+        "{}: {} compile commands recovered",
+        path.display(),
+        compile_commands.len()
+    );
+    if compile_commands.is_empty() {
+        error!("{}: No compiler invocation found", path.display());
+        process::exit(1);
+    }
+    compile_commands
+}
+
+fn dump_options(args: &Cli) -> DumpOptions {
+    DumpOptions {
+        backend: match (&args.clang_scan_deps, args.scanner) {
+            _ if args.from_ninja_deps || args.ninja_deps.is_some() => {
+                Backend::NinjaDeps(args.ninja_deps.clone())
             }
-            Err(why) => {
-                error!("{:?}", why)
+            _ if args.from_depfiles || args.depfile_pattern.is_some() => {
+                Backend::BuildDepfiles(args.depfile_pattern.clone())
             }
+            (Some(executable), _) => Backend::ClangScanDeps(executable.clone()),
+            (None, Some(ScannerMode::Superset)) => Backend::Scanner(ScanMode::Superset),
+            (None, Some(ScannerMode::Evaluate)) => Backend::Scanner(ScanMode::Evaluate),
+            (None, None) => Backend::Compiler,
+        },
+        include_tree: args.include_tree
+            || matches!(
+                args.command,
+                CliSubCommand::Tree | CliSubCommand::Why { .. }
+            ),
+        system_prefixes: args.system_prefixes.clone(),
+        user_headers_only: args.exclude_system_headers,
+        launchers: args.launchers.clone(),
+        msvc_include_prefix: args.msvc_include_prefix.clone(),
+        modules: args.modules || matches!(args.command, CliSubCommand::Modules),
+    }
+}
+
+/// Compile commands reported separately from the others.
+struct Project {
+    /// Database the commands come from. `None` if all databases are merged into one.
+    database: Option<PathBuf>,
+    compile_commands: Vec<CompileCommand>,
+    /// Results extracted while loading the commands.
+    translation_units: Option<Vec<TranslationUnit>>,
+}
+
+/// Fixed-size key to drop duplicated entries as `merge_databases` does, without keeping them.
+fn entry_key(command: &CompileCommand) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (
+        command.path(),
+        &command.directory,
+        &command.output,
+        command.arguments().ok(),
+    )
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the database to decode and extract dependencies from at the same time, i.e. the only
+/// one given.
+fn streamed_database(args: &Cli) -> Option<&str> {
+    match (args.compile_commands.as_slice(), &args.command) {
+        (_, CliSubCommand::Shared) => None,
+        ([path], _) if args.discover.is_none() && args.from_build_log.is_none() => Some(path),
+        _ => None,
+    }
+}
+
+/// Whether the subcommand needs the compile commands, e.g. to label variants.
+fn keeps_compile_commands(args: &Cli) -> bool {
+    args.write_compile_commands.is_some()
+        || args.variants == Variants::Each
+        || matches!(args.command, CliSubCommand::Rdeps { .. })
+}
+
+/// Extracts dependencies of the commands in the database at `path` while the rest is still being
+/// decoded, and passes them to `consume` in the order they finish.
+///
+/// Exits if the database is unreadable, malformed or has no valid command.
+fn stream_dependencies<F>(path: &str, options: &DumpOptions, mut consume: F)
+where
+    F: FnMut(usize, CompileCommand, TranslationUnit) + Send,
+{
+    let entries = match stream_database(Path::new(path)) {
+        Ok(entries) => entries,
+        Err(why) => {
+            error!("{}: {}", path, why);
+            process::exit(1);
         }
+    };
+    let mut failed = false;
+    let mut done = HashSet::new();
+    let commands = entries.filter_map(|entry| match entry {
+        Ok(command) => done.insert(entry_key(&command)).then_some(command),
+        Err(why @ Error::InvalidEntryError(..)) => {
+            error!("{}: {}", path, why);
+            None
+        }
+        Err(why) => {
+            error!("{}: {}", path, why);
+            failed = true;
+            None
+        }
+    });
+    let mut count = 0;
+    dump_dependencies_streaming(commands, options, |i, command, unit| {
+        count += 1;
+        consume(i, command, unit);
+    });
+    if failed {
+        process::exit(1);
+    }
+    if count == 0 {
+        error!("{}: No valid compile command", path);
+        process::exit(1);
+    }
+}
+
+/// Loads the only database and extracts dependencies while the rest is still being decoded.
+///
+/// Commands are kept only if the subcommand needs them.
+fn stream_project(args: &Cli, path: &str, options: &DumpOptions) -> Project {
+    let keep = keeps_compile_commands(args);
+    let mut results = Vec::new();
+    stream_dependencies(path, options, |i, command, unit| {
+        results.push((i, keep.then_some(command), unit));
+    });
+    results.sort_by_key(|v| v.0);
+    let (compile_commands, mut translation_units): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .map(|(_, command, unit)| (command, unit))
+        .unzip();
+    let compile_commands: Vec<_> = compile_commands.into_iter().flatten().collect();
+    if keep {
+        label_variants(&compile_commands, &mut translation_units);
+    }
+    Project {
+        database: None,
+        compile_commands,
+        translation_units: Some(translation_units),
     }
-    let mut dependency_list: Vec<_> = dependency_list.iter().collect();
-    dependency_list.sort();
+}
+
+/// `list` on the only database, keeping only the dependencies listed so far instead of each
+/// translation unit.
+fn stream_list(args: &Cli, path: &str, options: &DumpOptions) {
+    let mut dependency_list = BTreeSet::new();
+    stream_dependencies(path, options, |_, _, unit| {
+        if let Some(ref why) = unit.error {
+            error!("{}: {}", unit.file.display(), why);
+        }
+        for v in args.report(&unit).dependencies {
+            dependency_list.insert(v.path);
+        }
+    });
     for path in dependency_list {
         println!("{}", path.display());
     }
 }
+
+fn load_projects(args: &Cli, options: &DumpOptions) -> Vec<Project> {
+    if let Some(ref root) = args.discover {
+        let databases = match discover_databases(root) {
+            Ok(databases) => databases,
+            Err(why) => {
+                error!("{}: {}", root.display(), why);
+                process::exit(1);
+            }
+        };
+        let projects: Vec<_> = databases
+            .into_iter()
+            .filter_map(|database| {
+                let compile_commands = load_entries(&database.display().to_string())?;
+                Some(Project {
+                    database: Some(database),
+                    compile_commands,
+                    translation_units: None,
+                })
+            })
+            .collect();
+        if projects.is_empty() {
+            error!("{}: No compile_commands.json found", root.display());
+            process::exit(1);
+        }
+        return projects;
+    }
+    if let Some(ref log) = args.from_build_log {
+        return vec![Project {
+            database: None,
+            compile_commands: load_build_log(log),
+            translation_units: None,
+        }];
+    }
+    if let CliSubCommand::Shared = args.command {
+        return args
+            .compile_commands
+            .iter()
+            .map(|path| Project {
+                database: Some(PathBuf::from(path)),
+                compile_commands: load_databases(std::slice::from_ref(path)),
+                translation_units: None,
+            })
+            .collect();
+    }
+    if let Some(path) = streamed_database(args) {
+        return vec![stream_project(args, path, options)];
+    }
+    vec![Project {
+        database: None,
+        compile_commands: load_databases(&args.compile_commands),
+        translation_units: None,
+    }]
+}
+
+fn dump_project(args: &Cli, options: &DumpOptions, project: &mut Project) -> Vec<TranslationUnit> {
+    let translation_units = match project.translation_units.take() {
+        Some(translation_units) => translation_units,
+        None => dump_dependencies(&project.compile_commands, options),
+    };
+
+    for unit in translation_units.iter() {
+        if let Some(ref why) = unit.error {
+            error!("{}: {}", unit.file.display(), why);
+        }
+    }
+
+    match args.command {
+        CliSubCommand::Rdeps { .. } => translation_units,
+        _ => merge_variants(
+            translation_units,
+            match args.variants {
+                Variants::Union => VariantMode::Union,
+                Variants::Intersection => VariantMode::Intersection,
+                Variants::Each => VariantMode::Each,
+            },
+        ),
+    }
+}
+
+/// Runs the subcommand on a project. Returns the report unless the format is text.
+fn run(
+    args: &Cli,
+    compile_commands: &[CompileCommand],
+    translation_units: Vec<TranslationUnit>,
+) -> Option<Report> {
+    match args.command {
+        CliSubCommand::List => list(args, translation_units),
+        CliSubCommand::Deps => deps(args, translation_units),
+        CliSubCommand::Rdeps { ref paths, print } => {
+            rdeps(args, paths, print, compile_commands, translation_units)
+        }
+        CliSubCommand::Tree => tree(args, translation_units),
+        CliSubCommand::Modules => modules(args, translation_units),
+        CliSubCommand::Why {
+            ref header,
+            ref from,
+            all,
+        } => why(args, header, from.as_deref(), all, translation_units),
+        CliSubCommand::Shared => unreachable!(),
+        CliSubCommand::Graph {
+            cluster_by_directory,
+            collapse_system_headers,
+        } => {
+            let options = GraphOptions {
+                cluster_by_directory,
+                collapse_system_headers,
+            };
+            graph(args, options, translation_units);
+            None
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = Cli::parse();
+    info!("args = {:?}", env::args());
+
+    let options = dump_options(&args);
+    if let (Some(path), CliSubCommand::List, Format::Text, false) = (
+        streamed_database(&args),
+        &args.command,
+        args.format,
+        args.variants == Variants::Intersection || args.write_compile_commands.is_some(),
+    ) {
+        return stream_list(&args, path, &options);
+    }
+    let mut projects = load_projects(&args, &options);
+    if let Some(ref path) = args.write_compile_commands {
+        let compile_commands: Vec<_> = projects
+            .iter()
+            .flat_map(|v| v.compile_commands.iter())
+            .collect();
+        let content = serde_json::to_string_pretty(&compile_commands).expect("Failed to serialize");
+        if let Err(why) = fs::write(path, content + "\n") {
+            error!("{}: {}", path.display(), why);
+            process::exit(1);
+        }
+    }
+
+    if let CliSubCommand::Shared = args.command {
+        let projects = projects
+            .iter_mut()
+            .map(|project| {
+                let database = project.database.clone().unwrap_or_default();
+                (database, dump_project(&args, &options, project))
+            })
+            .collect();
+        return shared(&args, projects);
+    }
+
+    let mut reports = Vec::new();
+    for project in projects.iter_mut() {
+        let translation_units = dump_project(&args, &options, project);
+        if let (Some(ref database), Format::Text) = (&project.database, args.format) {
+            println!("# {}", database.display());
+        }
+        if let Some(report) = run(&args, &project.compile_commands, translation_units) {
+            reports.push((project.database.clone(), report));
+        }
+    }
+    match (reports.len(), args.discover.is_some()) {
+        (0, _) => (),
+        (1, false) => print_report(args.format, reports.pop().unwrap().1),
+        _ => {
+            let projects = reports
+                .into_iter()
+                .map(|(database, report)| ProjectReport {
+                    database: database.unwrap_or_default(),
+                    report,
+                })
+                .collect();
+            print_discovery_report(args.format, DiscoveryReport::new(projects))
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use log::{trace, warn};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use crate::arguments::{remove_launchers, remove_msvc_dependency_flags, remove_msvc_output_flags};
+use crate::compile_command::CompileCommand;
+use crate::compiler::Compiler;
+use crate::dependency::run_compiler;
+use crate::error::Result;
+use crate::include_tree::{flatten_includes, insert_include, Include};
+use crate::p1689::{dump_msvc_modules, ModuleDependencies};
+
+/// cl and clang-cl.
+#[derive(Debug, Clone)]
+pub struct Msvc {
+    /// Prefix of `/showIncludes` lines. Localized by cl.
+    pub include_prefix: String,
+}
+
+impl Default for Msvc {
+    fn default() -> Self {
+        Self {
+            include_prefix: String::from(Self::DEFAULT_INCLUDE_PREFIX),
+        }
+    }
+}
+
+impl Msvc {
+    pub const DEFAULT_INCLUDE_PREFIX: &'static str = "Note: including file:";
+
+    fn is_clang_cl(args: &[String]) -> bool {
+        Path::new(&args[0])
+            .file_stem()
+            .and_then(|v| v.to_str())
+            .map(|v| v.eq_ignore_ascii_case("clang-cl"))
+            .unwrap_or(false)
+            || args.iter().any(|v| v == "--driver-mode=cl")
+    }
+
+    /// Runs the compile command with `/showIncludes /Zs` and returns its include tree.
+    fn show_includes(
+        &self,
+        command: &CompileCommand,
+        user_headers_only: bool,
+    ) -> Result<Vec<Include>> {
+        let args = remove_launchers(&command.arguments()?, &[]);
+        let mut args = remove_msvc_output_flags(&remove_msvc_dependency_flags(&args));
+        // cl does not support `/showIncludes:user`
+        let show_includes = if user_headers_only && Self::is_clang_cl(&args) {
+            "/showIncludes:user"
+        } else {
+            "/showIncludes"
+        };
+        args.insert(1, String::from(show_includes));
+        args.insert(2, String::from("/Zs"));
+        trace!("show_includes: args={:?}", args);
+
+        let output = run_compiler(command, &args)?;
+        let (mut includes, mut messages) = parse_show_includes(
+            output.stdout.as_slice(),
+            &command.directory,
+            &self.include_prefix,
+        )?;
+        let (others, more) = parse_show_includes(
+            output.stderr.as_slice(),
+            &command.directory,
+            &self.include_prefix,
+        )?;
+        includes.extend(others);
+        messages.extend(more);
+        // cl echoes the file name of the source
+        let file = command.file.to_string_lossy();
+        let name = file.rsplit(['/', '\\']).next().unwrap_or(&file);
+        for message in messages.iter().filter(|v| v.as_str() != name) {
+            warn!("{}: {}", command.file.display(), message);
+        }
+        output.status.exit_ok()?;
+        Ok(includes)
+    }
+}
+
+impl Compiler for Msvc {
+    fn dependencies(
+        &self,
+        command: &CompileCommand,
+        user_headers_only: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let includes = self.show_includes(command, user_headers_only)?;
+        Ok(flatten_includes(command, &includes))
+    }
+
+    fn include_tree(&self, command: &CompileCommand) -> Result<Vec<Include>> {
+        self.show_includes(command, false)
+    }
+
+    fn modules(&self, command: &CompileCommand) -> Result<ModuleDependencies> {
+        dump_msvc_modules(command)
+    }
+}
+
+/// Parses output of `/showIncludes` and returns the include tree of the main source file with the
+/// other non-empty lines, such as diagnostics and the file name cl echoes.
+///
+/// Depth of a header is given by the number of spaces after `prefix`. Relative paths are resolved
+/// against `directory`.
+pub fn parse_show_includes<R: BufRead>(
+    output: R,
+    directory: &Path,
+    prefix: &str,
+) -> Result<(Vec<Include>, Vec<String>)> {
+    let mut result = Vec::new();
+    let mut messages = Vec::new();
+    for line in output.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        let note = match line.strip_prefix(prefix) {
+            Some(note) => note,
+            None => {
+                if !line.is_empty() {
+                    messages.push(String::from(line));
+                }
+                continue;
+            }
+        };
+        let depth = note.bytes().take_while(|c| *c == b' ').count();
+        if depth == 0 {
+            continue;
+        }
+        let path = directory.join(&note[depth..]);
+        let path = path.canonicalize().unwrap_or(path);
+        trace!("parse_show_includes: depth={}, path={:?}", depth, path);
+        insert_include(&mut result, depth, Include::new(path));
+    }
+    Ok((result, messages))
+}
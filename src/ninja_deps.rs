@@ -0,0 +1,236 @@
+//! Binary dependency log `.ninja_deps` Ninja writes for rules with `deps = gcc` or `deps = msvc`.
+use log::{trace, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::arguments::{output_argument, remove_launchers};
+use crate::compile_command::CompileCommand;
+use crate::dependency::resolve_dependencies;
+use crate::error::{Error, Result};
+
+const SIGNATURE: &[u8] = b"# ninjadeps\n";
+/// Same limit as Ninja. Larger records mean the log is corrupted.
+const MAX_RECORD_SIZE: usize = (1 << 19) - 1;
+/// Name of the log in the build directory.
+pub const NINJA_DEPS_FILE_NAME: &str = ".ninja_deps";
+
+/// Dependencies of an output recorded in `.ninja_deps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NinjaDepsRecord {
+    /// Path as written in `build.ninja`, usually relative to the build directory.
+    pub output: PathBuf,
+    /// Modification time of the output when recorded, in Ninja's unit of the platform.
+    pub mtime: u64,
+    /// Paths as recorded, including the source file.
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// Content of `.ninja_deps`. Only the last record of each output is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NinjaDeps {
+    pub version: u32,
+    records: Vec<NinjaDepsRecord>,
+    /// Indices of `records` by normalized output.
+    index: HashMap<PathBuf, usize>,
+}
+
+impl NinjaDeps {
+    fn new(version: u32, records: Vec<NinjaDepsRecord>) -> Self {
+        let mut index = HashMap::with_capacity(records.len());
+        for (i, record) in records.iter().enumerate() {
+            index.entry(normalize(&record.output)).or_insert(i);
+        }
+        Self {
+            version,
+            records,
+            index,
+        }
+    }
+
+    /// Records in the order the paths of their outputs appear in the log.
+    pub fn records(&self) -> &[NinjaDepsRecord] {
+        &self.records
+    }
+
+    /// Returns the record of `output`, compared after removing `.` and `..` components.
+    pub fn find(&self, output: &Path) -> Option<&NinjaDepsRecord> {
+        let i = self.index.get(&normalize(output))?;
+        Some(&self.records[*i])
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir
+                if matches!(result.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                result.pop();
+            }
+            _ => result.push(component),
+        }
+    }
+    result
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn read_u32(content: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(content[offset..offset + 4].try_into().unwrap())
+}
+
+/// Parses `.ninja_deps` of version 3 or 4.
+///
+/// Like Ninja, a truncated or corrupted tail is ignored with a warning.
+pub fn parse_ninja_deps(content: &[u8]) -> Result<NinjaDeps> {
+    if !content.starts_with(SIGNATURE) || content.len() < SIGNATURE.len() + 4 {
+        return Err(Error::NinjaDepsFormatError("missing signature"));
+    }
+    let version = read_u32(content, SIGNATURE.len());
+    let ids_per_header = match version {
+        3 => 2,
+        4 => 3,
+        _ => return Err(Error::NinjaDepsFormatError("unsupported version")),
+    };
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut records: Vec<Option<NinjaDepsRecord>> = Vec::new();
+    let mut offset = SIGNATURE.len() + 4;
+    while offset < content.len() {
+        if content.len() - offset < 4 {
+            warn!("Truncated record in .ninja_deps at {}", offset);
+            break;
+        }
+        let header = read_u32(content, offset);
+        let is_deps = header >> 31 != 0;
+        let size = (header & 0x7FFF_FFFF) as usize;
+        offset += 4;
+        if size > MAX_RECORD_SIZE || !size.is_multiple_of(4) || content.len() - offset < size {
+            warn!("Corrupted record in .ninja_deps at {}", offset - 4);
+            break;
+        }
+        let record = &content[offset..offset + size];
+        offset += size;
+
+        if is_deps {
+            if size < ids_per_header * 4 {
+                warn!(
+                    "Corrupted dependency record in .ninja_deps at {}",
+                    offset - size
+                );
+                break;
+            }
+            let ids: Vec<usize> = (0..size / 4)
+                .map(|i| read_u32(record, i * 4) as usize)
+                .collect();
+            let mtime = match version {
+                3 => ids[1] as u64,
+                _ => (ids[2] as u64) << 32 | ids[1] as u64,
+            };
+            let (output, inputs) = (ids[0], &ids[ids_per_header..]);
+            if output >= paths.len() || inputs.iter().any(|v| *v >= paths.len()) {
+                warn!("Unknown path id in .ninja_deps at {}", offset - size);
+                break;
+            }
+            if records.len() <= output {
+                records.resize(output + 1, None);
+            }
+            records[output] = Some(NinjaDepsRecord {
+                output: paths[output].clone(),
+                mtime,
+                dependencies: inputs.iter().map(|v| paths[*v].clone()).collect(),
+            });
+        } else {
+            if size < 4 {
+                warn!("Corrupted path record in .ninja_deps at {}", offset - size);
+                break;
+            }
+            let checksum = read_u32(record, size - 4);
+            if !checksum as usize != paths.len() {
+                warn!("Path id mismatch in .ninja_deps at {}", offset - size);
+                break;
+            }
+            let mut path = &record[..size - 4];
+            while let Some(rest) = path.strip_suffix(b"\0") {
+                path = rest;
+            }
+            paths.push(PathBuf::from(String::from_utf8_lossy(path).into_owned()));
+        }
+    }
+    Ok(NinjaDeps::new(
+        version,
+        records.into_iter().flatten().collect(),
+    ))
+}
+
+/// Reads `.ninja_deps` at `path`.
+pub fn read_ninja_deps(path: &Path) -> Result<NinjaDeps> {
+    parse_ninja_deps(&fs::read(path)?)
+}
+
+/// Looks up dependencies of each command in `.ninja_deps` by its output, and returns them in the
+/// same order. `None` for commands without a record.
+///
+/// `log` is used for all commands if given. Otherwise `.ninja_deps` in the directory of each
+/// command is used. Paths in a log are relative to the directory it is in.
+pub fn ninja_dependencies(
+    commands: &[CompileCommand],
+    log: Option<&Path>,
+) -> Vec<Option<Vec<PathBuf>>> {
+    let mut logs: HashMap<PathBuf, Option<NinjaDeps>> = HashMap::new();
+    let mut result = Vec::new();
+    for command in commands.iter() {
+        let path = match log {
+            Some(log) => log.to_path_buf(),
+            None => command.directory.join(NINJA_DEPS_FILE_NAME),
+        };
+        let deps = logs
+            .entry(path.clone())
+            .or_insert_with(|| match read_ninja_deps(&path) {
+                Ok(deps) => Some(deps),
+                Err(why) => {
+                    warn!("Failed to read {:?}: {}", path, why);
+                    None
+                }
+            });
+        let build_directory = canonicalize(
+            path.parent()
+                .filter(|v| !v.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new(".")),
+        );
+        let output = command.output.clone().or_else(|| {
+            let args = remove_launchers(&command.arguments().ok()?, &[]);
+            output_argument(&args).map(PathBuf::from)
+        });
+        let record = match (deps, output) {
+            (Some(deps), Some(output)) => {
+                let output = normalize(&canonicalize(&command.directory).join(output));
+                match output.strip_prefix(&build_directory) {
+                    Ok(relative) => deps.find(relative).or_else(|| deps.find(&output)),
+                    Err(_) => deps.find(&output),
+                }
+            }
+            _ => None,
+        };
+        trace!("ninja_dependencies: {:?} => {:?}", command.file, record);
+        result.push(record.and_then(|record| {
+            let paths = record.dependencies.iter().map(PathBuf::as_path).collect();
+            match resolve_dependencies(paths, &build_directory) {
+                Ok(dependencies) => Some(dependencies),
+                Err(why) => {
+                    warn!(
+                        "Failed to resolve dependencies of {:?}: {}",
+                        command.file, why
+                    );
+                    None
+                }
+            }
+        }));
+    }
+    result
+}
@@ -0,0 +1,207 @@
+//! C++20 module dependencies in P1689R5 format.
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::arguments::{
+    output_argument, remove_dependency_flags, remove_launchers, remove_msvc_dependency_flags,
+    remove_msvc_output_flags,
+};
+use crate::compile_command::CompileCommand;
+use crate::dependency::{rewrite_arguments, run_compiler, temporary_path};
+use crate::error::Result;
+
+/// Document of P1689R5 written by `clang-scan-deps -format=p1689`, gcc `-fdeps-format=p1689r5`
+/// and cl `/scanDependencies`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct P1689 {
+    pub version: u32,
+    #[serde(default)]
+    pub revision: u32,
+    pub rules: Vec<P1689Rule>,
+}
+
+/// Module dependencies of a translation unit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct P1689Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub work_directory: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_output: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provides: Vec<ProvidedModule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<RequiredModule>,
+}
+
+fn is_interface_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProvidedModule {
+    pub logical_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiled_module_path: Option<PathBuf>,
+    #[serde(default = "is_interface_default")]
+    pub is_interface: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LookupMethod {
+    /// `import name;`
+    #[default]
+    ByName,
+    /// `import <header>;`
+    IncludeAngle,
+    /// `import "header";`
+    IncludeQuote,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RequiredModule {
+    pub logical_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiled_module_path: Option<PathBuf>,
+    #[serde(default)]
+    pub lookup_method: LookupMethod,
+}
+
+/// C++20 modules a translation unit provides and imports, by logical name.
+///
+/// Header units are named as written, e.g. `<vector>`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ModuleDependencies {
+    pub provides: Vec<String>,
+    pub requires: Vec<String>,
+}
+
+impl P1689 {
+    /// Returns modules of all rules without duplicates.
+    pub fn modules(&self) -> ModuleDependencies {
+        let mut result = ModuleDependencies::default();
+        for rule in self.rules.iter() {
+            for module in rule.provides.iter() {
+                if !result.provides.contains(&module.logical_name) {
+                    result.provides.push(module.logical_name.clone());
+                }
+            }
+            for module in rule.requires.iter() {
+                if !result.requires.contains(&module.logical_name) {
+                    result.requires.push(module.logical_name.clone());
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Parses P1689R5 document.
+pub fn parse_p1689(content: &str) -> Result<P1689> {
+    Ok(serde_json::from_str(content)?)
+}
+
+fn is_clang(args: &[String]) -> bool {
+    Path::new(&args[0])
+        .file_name()
+        .and_then(|v| v.to_str())
+        .map(|v| v.contains("clang"))
+        .unwrap_or(false)
+}
+
+/// Returns `clang-scan-deps` next to the compiler if exists, or the one in `PATH`.
+fn clang_scan_deps(compiler: &str) -> PathBuf {
+    let sibling = Path::new(compiler).with_file_name("clang-scan-deps");
+    if sibling.is_absolute() && sibling.is_file() {
+        sibling
+    } else {
+        PathBuf::from("clang-scan-deps")
+    }
+}
+
+/// Asks the gcc-style compile command for C++20 module dependencies.
+///
+/// Uses `clang-scan-deps -format=p1689` for clang and `-fdeps-format=p1689r5` for gcc. Compilers
+/// not named after clang, e.g. `c++` of Xcode, fall back to `clang-scan-deps` if the gcc way fails.
+pub fn dump_modules(command: &CompileCommand) -> Result<ModuleDependencies> {
+    let args = remove_launchers(&command.arguments()?, &[]);
+    if is_clang(&args) {
+        return dump_clang_modules(command, &args);
+    }
+    dump_gcc_modules(command).or_else(|why| {
+        trace!("dump_modules: {}; trying clang-scan-deps", why);
+        dump_clang_modules(command, &args).map_err(|_| why)
+    })
+}
+
+fn dump_clang_modules(command: &CompileCommand, args: &[String]) -> Result<ModuleDependencies> {
+    // clang-scan-deps names the rule after the output, and never writes it
+    let mut args = remove_dependency_flags(args);
+    if output_argument(&args).is_none() {
+        if let Some(ref output) = command.output {
+            args.extend([String::from("-o"), output.display().to_string()]);
+        }
+    }
+    let executable = clang_scan_deps(&args[0]);
+    trace!("dump_modules: {:?} -format=p1689 -- {:?}", executable, args);
+    let output = Command::new(executable)
+        .args(["-format=p1689", "--"])
+        .args(&args)
+        .current_dir(&command.directory)
+        .output()?;
+    output.status.exit_ok()?;
+    Ok(parse_p1689(&String::from_utf8_lossy(&output.stdout))?.modules())
+}
+
+fn dump_gcc_modules(command: &CompileCommand) -> Result<ModuleDependencies> {
+    let path = temporary_path("p1689.json");
+    let target = command
+        .output
+        .as_ref()
+        .map(|v| v.display().to_string())
+        .unwrap_or_else(|| String::from("a.o"));
+    let deps_file = format!("-fdeps-file={}", path.display());
+    let deps_target = format!("-fdeps-target={}", target);
+    let mut args = rewrite_arguments(
+        command,
+        &["-E", "-fdeps-format=p1689r5", &deps_file, &deps_target],
+    )?;
+    args.extend([String::from("-o"), String::from("/dev/null")]);
+    trace!("dump_modules: args={:?}", args);
+    let output = run_compiler(command, &args);
+    let content = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    output?.status.exit_ok()?;
+    Ok(parse_p1689(&content?)?.modules())
+}
+
+/// Asks cl with `/scanDependencies` for C++20 module dependencies.
+pub(crate) fn dump_msvc_modules(command: &CompileCommand) -> Result<ModuleDependencies> {
+    let args = remove_launchers(&command.arguments()?, &[]);
+    let mut args = remove_msvc_output_flags(&remove_msvc_dependency_flags(&args));
+    let path = temporary_path("p1689.json");
+    // Keep the object file, if any, out of the build tree
+    let object = temporary_path("scan.obj");
+    args.insert(1, String::from("/scanDependencies"));
+    args.insert(2, path.display().to_string());
+    args.insert(3, format!("/Fo{}", object.display()));
+    trace!("dump_msvc_modules: args={:?}", args);
+    let output = run_compiler(command, &args);
+    let content = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&object);
+    output?.status.exit_ok()?;
+    Ok(parse_p1689(&content?)?.modules())
+}
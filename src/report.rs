@@ -0,0 +1,288 @@
+//! Machine readable output of `--format json|ndjson`.
+//!
+//! Every document and record carries [`SCHEMA_VERSION`]. It is bumped on incompatible changes
+//! only; new fields may be added without bumping it.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::dependency::{DependencyKind, TranslationUnit};
+use crate::include_tree::Include;
+use crate::p1689::ModuleDependencies;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DependencyReport {
+    pub path: PathBuf,
+    pub kind: DependencyKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TranslationUnitReport {
+    /// Source file as written in the compile command. May be relative to `directory`.
+    pub file: PathBuf,
+    /// `file` resolved against `directory`. Canonicalized if exists.
+    #[serde(default)]
+    pub path: PathBuf,
+    pub directory: PathBuf,
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    /// Tells apart commands for the same file. Given for files compiled more than once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    pub dependencies: Vec<DependencyReport>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Whether dependencies come from a stale depfile of the build.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    /// Include tree. Given in `--include-tree` mode only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub includes: Option<Vec<Include>>,
+    /// C++20 modules. Given in `--modules` mode only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modules: Option<ModuleDependencies>,
+}
+
+impl TranslationUnitReport {
+    /// Reports dependencies of `unit` that `filter` accepts.
+    pub fn new<F>(unit: &TranslationUnit, filter: F) -> Self
+    where
+        F: Fn(&Path, DependencyKind) -> bool,
+    {
+        let dependencies = unit
+            .dependencies
+            .iter()
+            .map(|v| DependencyReport {
+                path: v.clone(),
+                kind: unit.classify(v),
+            })
+            .filter(|v| filter(&v.path, v.kind))
+            .collect();
+        Self {
+            file: unit.file.clone(),
+            path: unit.path.clone(),
+            directory: unit.directory.clone(),
+            output: unit.output.clone(),
+            variant: unit.variant.clone(),
+            dependencies,
+            error: unit.error.as_ref().map(|v| v.to_string()),
+            stale: unit.stale,
+            includes: unit.includes.clone(),
+            modules: unit.modules.clone(),
+        }
+    }
+}
+
+/// Include chain from the main source file of a translation unit to a header.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChainReport {
+    pub file: PathBuf,
+    /// Headers in order of inclusion. The last one is the queried header.
+    pub chain: Vec<PathBuf>,
+}
+
+/// C++20 module with translation units that provide and import it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModuleReport {
+    pub name: String,
+    pub provided_by: Vec<PathBuf>,
+    pub imported_by: Vec<PathBuf>,
+}
+
+impl ModuleReport {
+    /// Collects modules of `units`, sorted by name.
+    pub fn collect(units: &[TranslationUnit]) -> Vec<ModuleReport> {
+        let mut modules: BTreeMap<&str, ModuleReport> = BTreeMap::new();
+        for unit in units.iter() {
+            let dependencies = match unit.modules {
+                Some(ref dependencies) => dependencies,
+                None => continue,
+            };
+            let names = dependencies
+                .provides
+                .iter()
+                .map(|v| (v, true))
+                .chain(dependencies.requires.iter().map(|v| (v, false)));
+            for (name, provides) in names {
+                let report = modules.entry(name).or_insert_with(|| ModuleReport {
+                    name: name.clone(),
+                    provided_by: Vec::new(),
+                    imported_by: Vec::new(),
+                });
+                if provides {
+                    report.provided_by.push(unit.file.clone());
+                } else {
+                    report.imported_by.push(unit.file.clone());
+                }
+            }
+        }
+        modules.into_values().collect()
+    }
+}
+
+/// Header that translation units of more than one project depend on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SharedHeaderReport {
+    pub path: PathBuf,
+    pub kind: DependencyKind,
+    /// Databases of the projects, in the order given.
+    pub projects: Vec<PathBuf>,
+}
+
+impl SharedHeaderReport {
+    /// Collects dependencies other than main sources that `filter` accepts and translation units
+    /// of more than one project depend on, sorted by path.
+    pub fn collect<F>(projects: &[(PathBuf, &[TranslationUnit])], filter: F) -> Vec<Self>
+    where
+        F: Fn(&Path, DependencyKind) -> bool,
+    {
+        let mut headers: BTreeMap<&Path, SharedHeaderReport> = BTreeMap::new();
+        for (database, units) in projects.iter() {
+            for unit in units.iter() {
+                let source = match unit.source() {
+                    Some(source) => source.to_path_buf(),
+                    None => unit.directory.join(&unit.file),
+                };
+                for path in unit.dependencies.iter() {
+                    let kind = unit.classify(path);
+                    if *path == source || !filter(path, kind) {
+                        continue;
+                    }
+                    let report = headers.entry(path).or_insert_with(|| SharedHeaderReport {
+                        path: path.clone(),
+                        kind,
+                        projects: Vec::new(),
+                    });
+                    if !report.projects.contains(database) {
+                        report.projects.push(database.clone());
+                    }
+                }
+            }
+        }
+        headers
+            .into_values()
+            .filter(|v| v.projects.len() > 1)
+            .collect()
+    }
+}
+
+/// Document of `--format json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub version: u32,
+    /// Dependencies of all translation units. Given by `list` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<Vec<DependencyReport>>,
+    /// Include chains. Given by `why` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chains: Option<Vec<ChainReport>>,
+    /// C++20 modules. Given by `modules` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Vec<ModuleReport>>,
+    pub translation_units: Vec<TranslationUnitReport>,
+}
+
+impl Report {
+    pub fn new(translation_units: Vec<TranslationUnitReport>) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            dependencies: None,
+            chains: None,
+            modules: None,
+            translation_units,
+        }
+    }
+
+    /// Splits into records of `--format ndjson`.
+    pub fn into_records(self) -> Vec<VersionedRecord> {
+        self.into_records_of(None)
+    }
+
+    fn into_records_of(self, database: Option<&Path>) -> Vec<VersionedRecord> {
+        let dependencies = self.dependencies.unwrap_or_default();
+        let chains = self.chains.unwrap_or_default();
+        let modules = self.modules.unwrap_or_default();
+        dependencies
+            .into_iter()
+            .map(Record::Dependency)
+            .chain(chains.into_iter().map(Record::Chain))
+            .chain(modules.into_iter().map(Record::Module))
+            .chain(
+                self.translation_units
+                    .into_iter()
+                    .map(Record::TranslationUnit),
+            )
+            .map(|record| VersionedRecord {
+                version: self.version,
+                database: database.map(Path::to_path_buf),
+                record,
+            })
+            .collect()
+    }
+}
+
+/// Results of a project, i.e. a compilation database.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProjectReport {
+    pub database: PathBuf,
+    pub report: Report,
+}
+
+/// Document of `--format json` for several projects, e.g. with `--discover`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryReport {
+    pub version: u32,
+    pub projects: Vec<ProjectReport>,
+    /// Headers shared between projects. Given by `shared` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_headers: Option<Vec<SharedHeaderReport>>,
+}
+
+impl DiscoveryReport {
+    pub fn new(projects: Vec<ProjectReport>) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            projects,
+            shared_headers: None,
+        }
+    }
+
+    /// Splits into records of `--format ndjson`. Records of projects carry their database.
+    pub fn into_records(self) -> Vec<VersionedRecord> {
+        let mut records = Vec::new();
+        for project in self.projects {
+            records.extend(project.report.into_records_of(Some(&project.database)));
+        }
+        for header in self.shared_headers.unwrap_or_default() {
+            records.push(VersionedRecord {
+                version: self.version,
+                database: None,
+                record: Record::SharedHeader(header),
+            });
+        }
+        records
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Dependency(DependencyReport),
+    Chain(ChainReport),
+    Module(ModuleReport),
+    SharedHeader(SharedHeaderReport),
+    TranslationUnit(TranslationUnitReport),
+}
+
+/// Line of `--format ndjson`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionedRecord {
+    pub version: u32,
+    /// Database of the project the record belongs to. Given for several projects only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<PathBuf>,
+    #[serde(flatten)]
+    pub record: Record,
+}
@@ -0,0 +1,101 @@
+use log::{trace, warn};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::arguments::{remove_dependency_flags, remove_launchers};
+use crate::compile_command::CompileCommand;
+use crate::compiler::CompilerFamily;
+use crate::dependency::{resolve_dependencies, temporary_path};
+use crate::depfile::parse_depfile;
+use crate::error::Result;
+
+/// Make target given to each entry, so that rules in the output map back to compile commands.
+const TARGET_PREFIX: &str = "dump-dependency-target-";
+
+/// Runs `clang-scan-deps -format=make` once for all `commands` and returns dependencies of each
+/// command in the same order. `None` for commands it failed to scan.
+///
+/// `launchers` are skipped in addition to the known ones. cl-style commands are not scanned.
+pub fn scan_dependencies(
+    commands: &[CompileCommand],
+    executable: &Path,
+    launchers: &[String],
+) -> Result<Vec<Option<Vec<PathBuf>>>> {
+    let mut database = Vec::new();
+    for (i, command) in commands.iter().enumerate() {
+        let args = match command.arguments() {
+            Ok(args) => remove_launchers(&args, launchers),
+            Err(why) => {
+                warn!("Skip {:?}: {}", command.file, why);
+                continue;
+            }
+        };
+        if CompilerFamily::detect(&args) != CompilerFamily::Gcc {
+            continue;
+        }
+        let mut args = remove_dependency_flags(&args);
+        args.push(String::from("-MT"));
+        args.push(format!("{}{}", TARGET_PREFIX, i));
+        database.push(CompileCommand {
+            command: None,
+            arguments: Some(args),
+            ..command.clone()
+        });
+    }
+
+    let path = temporary_path("compile_commands.json");
+    fs::write(&path, serde_json::to_vec(&database)?)?;
+    let output = Command::new(executable)
+        .arg("-compilation-database")
+        .arg(&path)
+        .arg("-format=make")
+        .output();
+    fs::remove_file(&path)?;
+    let output = output?;
+    if !output.stderr.is_empty() {
+        // Tell human that a error occured
+        io::stderr().lock().write_all(&output.stderr)?;
+    }
+    if !output.status.success() {
+        warn!("clang-scan-deps failed for some entries: {}", output.status);
+    }
+
+    let mut result = vec![None; commands.len()];
+    let depfile = parse_depfile(&String::from_utf8_lossy(&output.stdout))?;
+    for rule in depfile.rules.iter() {
+        for target in rule.targets.iter() {
+            let i = match target
+                .to_str()
+                .and_then(|v| v.strip_prefix(TARGET_PREFIX))
+                .and_then(|v| v.parse::<usize>().ok())
+            {
+                Some(i) if i < commands.len() => i,
+                _ => {
+                    warn!("Unknown target in clang-scan-deps output: {:?}", target);
+                    continue;
+                }
+            };
+            let prerequisites = rule.prerequisites.iter().map(PathBuf::as_path).collect();
+            let dependencies = match resolve_dependencies(prerequisites, &commands[i].directory) {
+                Ok(dependencies) => dependencies,
+                Err(why) => {
+                    warn!(
+                        "Failed to resolve dependencies of {:?}: {}",
+                        commands[i].file, why
+                    );
+                    continue;
+                }
+            };
+            trace!(
+                "scan_dependencies: {:?} => {:?}",
+                commands[i].file,
+                dependencies
+            );
+            result[i] = Some(dependencies);
+        }
+    }
+    Ok(result)
+}
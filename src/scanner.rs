@@ -0,0 +1,847 @@
+//! Built-in include scanner that follows `#include` without running the compiler.
+
+use log::{info, trace};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use crate::compile_command::CompileCommand;
+use crate::compiler::Compiler;
+use crate::error::Result;
+use crate::include_tree::{flatten_includes, Include};
+use crate::system_header::{language, option_value, FALLBACK_SYSTEM_DIRECTORIES};
+
+/// Nesting limit of `#include`, same as gcc.
+const MAX_DEPTH: usize = 200;
+
+/// How the scanner treats conditional directives.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+    /// Ignore `#if` and `#define` and follow every `#include`. May report more than the compiler.
+    #[default]
+    Superset,
+    /// Evaluate simple `#if`, `#ifdef` and `#elif` with macros given by `-D`, `-U` and `#define`.
+    ///
+    /// Conditions that cannot be evaluated, such as those on macros predefined by the compiler,
+    /// are regarded as unknown and all of their branches are scanned.
+    Evaluate,
+}
+
+/// Resolves `#include` the way gcc does, reading the source and headers by itself.
+///
+/// Understands `-I`, `-iquote`, `-isystem`, `-idirafter`, `-include`, `-imacros`, `-nostdinc`,
+/// `-D` and `-U`. Headers of the toolchain are searched in [`FALLBACK_SYSTEM_DIRECTORIES`].
+/// Each header is scanned once per translation unit, as if every header had an include guard.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Scanner {
+    pub mode: ScanMode,
+}
+
+impl Scanner {
+    fn scan(&self, command: &CompileCommand, user_headers_only: bool) -> Result<Vec<Include>> {
+        let args = command.arguments()?;
+        let mut scan = Scan::new(self.mode, user_headers_only, command, &args);
+        let source = Header {
+            path: command.path().canonicalize()?,
+            index: None,
+            system: false,
+        };
+        scan.visited.insert(source.path.clone());
+
+        // `-include` files are searched in the working directory first, then in the quote chain
+        let command_line = Header {
+            path: command.directory.join("<command line>"),
+            index: None,
+            system: false,
+        };
+        let mut includes = Vec::new();
+        for name in mem::take(&mut scan.forced_includes) {
+            match scan.resolve(&name, false, &command_line, false) {
+                Some(header) => includes.extend(scan.enter(header, 1)),
+                None => info!("Header not found: {:?} given by -include", name),
+            }
+        }
+        includes.extend(scan.scan_file(&source, 1)?);
+        Ok(includes)
+    }
+}
+
+impl Compiler for Scanner {
+    fn dependencies(
+        &self,
+        command: &CompileCommand,
+        user_headers_only: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let includes = self.scan(command, user_headers_only)?;
+        Ok(flatten_includes(command, &includes))
+    }
+
+    fn include_tree(&self, command: &CompileCommand) -> Result<Vec<Include>> {
+        self.scan(command, false)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SearchDirectory {
+    path: PathBuf,
+    system: bool,
+}
+
+/// A file being scanned.
+#[derive(Debug, Clone)]
+struct Header {
+    /// Canonicalized.
+    path: PathBuf,
+    /// Position in the search chain where the file was found. Used by `#include_next`.
+    index: Option<usize>,
+    /// Whether the file is in a system directory.
+    system: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Macro {
+    /// Object-like macro with its replacement list.
+    Object(String),
+    /// Macro whose value is unknown, such as function-like macros.
+    Opaque,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Conditional {
+    /// Whether the enclosing group is active.
+    parent: bool,
+    /// Whether the current branch is scanned.
+    active: bool,
+    /// Whether a branch has been known to be true.
+    taken: bool,
+}
+
+struct Scan {
+    mode: ScanMode,
+    user_headers_only: bool,
+    /// `-iquote` directories followed by directories for `#include <...>`.
+    chain: Vec<SearchDirectory>,
+    /// Start of directories for `#include <...>` in `chain`.
+    bracket: usize,
+    forced_includes: Vec<String>,
+    macros: HashMap<String, Macro>,
+    /// Macros known to be undefined.
+    undefined: HashSet<String>,
+    visited: HashSet<PathBuf>,
+}
+
+/// Returns the leading identifier of `text`.
+fn identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+/// Whether `name` is reserved for the implementation, thus may be predefined by the compiler.
+fn is_reserved(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next() == Some('_')
+        && matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_uppercase())
+}
+
+impl Scan {
+    fn new(
+        mode: ScanMode,
+        user_headers_only: bool,
+        command: &CompileCommand,
+        args: &[String],
+    ) -> Self {
+        let mut quote = Vec::new();
+        let mut bracket = Vec::new();
+        let mut system = Vec::new();
+        let mut after = Vec::new();
+        let mut nostdinc = false;
+        let mut forced_includes = Vec::new();
+        let mut definitions = Vec::new();
+        let mut i = 1;
+        while i < args.len() {
+            if args[i] == "-nostdinc" {
+                nostdinc = true;
+            }
+            let mut consumed = 1;
+            for name in [
+                "-iquote",
+                "-isystem",
+                "-idirafter",
+                "-include",
+                "--include",
+                "-imacros",
+                "-I",
+                "--include-directory",
+                "-D",
+                "--define-macro",
+                "-U",
+                "--undefine-macro",
+            ] {
+                let (value, n) = match option_value(args, i, name) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let directory = command.directory.join(value);
+                match name {
+                    "-iquote" => quote.push(directory),
+                    "-isystem" => system.push(directory),
+                    "-idirafter" => after.push(directory),
+                    "-include" | "--include" | "-imacros" => {
+                        forced_includes.push(String::from(value))
+                    }
+                    "-I" | "--include-directory" if value != "-" => bracket.push(directory),
+                    "-D" | "--define-macro" => definitions.push(match value.split_once('=') {
+                        Some((name, value)) => format!("{} {}", name, value),
+                        None => format!("{} 1", value),
+                    }),
+                    "-U" | "--undefine-macro" => definitions.push(format!("#undef {}", value)),
+                    _ => {}
+                }
+                consumed = n;
+                break;
+            }
+            i += consumed;
+        }
+
+        let mut chain: Vec<SearchDirectory> = quote
+            .into_iter()
+            .map(|path| SearchDirectory {
+                path,
+                system: false,
+            })
+            .collect();
+        let start = chain.len();
+        let standard = if nostdinc {
+            Vec::new()
+        } else {
+            FALLBACK_SYSTEM_DIRECTORIES
+                .iter()
+                .map(PathBuf::from)
+                .collect()
+        };
+        let candidates = bracket
+            .into_iter()
+            .map(|path| (path, false))
+            .chain(system.into_iter().map(|path| (path, true)))
+            .chain(standard.into_iter().map(|path| (path, true)))
+            .chain(after.into_iter().map(|path| (path, true)))
+            .map(|(path, system)| SearchDirectory {
+                path: path.canonicalize().unwrap_or(path),
+                system,
+            })
+            .collect::<Vec<_>>();
+        for directory in candidates.iter() {
+            // gcc ignores duplicated directories, and `-I` of system directories
+            let duplicated = chain[start..].iter().any(|v| v.path == directory.path)
+                || (!directory.system
+                    && candidates
+                        .iter()
+                        .any(|v| v.system && v.path == directory.path));
+            if !duplicated {
+                chain.push(directory.clone());
+            }
+        }
+        trace!("Scan::new: chain={:?}", chain);
+
+        let mut scan = Self {
+            mode,
+            user_headers_only,
+            chain,
+            bracket: start,
+            forced_includes,
+            macros: HashMap::new(),
+            undefined: HashSet::new(),
+            visited: HashSet::new(),
+        };
+        scan.define("__STDC__ 1");
+        if language(args, &command.file) == "c++" {
+            scan.macros
+                .insert(String::from("__cplusplus"), Macro::Opaque);
+        } else {
+            scan.undefined.insert(String::from("__cplusplus"));
+        }
+        for definition in definitions {
+            match definition.strip_prefix("#undef ") {
+                Some(name) => scan.undefine(name),
+                None => scan.define(&definition),
+            }
+        }
+        scan
+    }
+
+    fn define(&mut self, definition: &str) {
+        let name = identifier(definition);
+        if name.is_empty() {
+            return;
+        }
+        let rest = &definition[name.len()..];
+        let value = if rest.starts_with('(') {
+            Macro::Opaque
+        } else {
+            Macro::Object(String::from(rest.trim()))
+        };
+        self.undefined.remove(name);
+        self.macros.insert(String::from(name), value);
+    }
+
+    fn undefine(&mut self, name: &str) {
+        let name = identifier(name);
+        self.macros.remove(name);
+        self.undefined.insert(String::from(name));
+    }
+
+    /// Whether `name` is defined. `None` if unknown.
+    fn defined(&self, name: &str) -> Option<bool> {
+        if self.mode == ScanMode::Superset {
+            None
+        } else if self.macros.contains_key(name)
+            || matches!(name, "__has_include" | "__has_include_next")
+        {
+            Some(true)
+        } else if self.undefined.contains(name) || !is_reserved(name) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Searches `name` included from `from` the way gcc does.
+    fn resolve(&self, name: &str, angled: bool, from: &Header, next: bool) -> Option<Header> {
+        if Path::new(name).is_absolute() {
+            let path = PathBuf::from(name);
+            return path.is_file().then(|| Header {
+                path: path.canonicalize().unwrap_or(path),
+                index: None,
+                system: false,
+            });
+        }
+        // `#include_next` in a file not found in the search chain works as `#include`
+        let start = match from.index {
+            Some(i) if next => i + 1,
+            _ if angled => self.bracket,
+            _ => {
+                let path = from.path.parent()?.join(name);
+                if path.is_file() {
+                    return Some(Header {
+                        path: path.canonicalize().unwrap_or(path),
+                        index: None,
+                        system: from.system,
+                    });
+                }
+                0
+            }
+        };
+        for (i, directory) in self.chain.iter().enumerate().skip(start) {
+            let path = directory.path.join(name);
+            if path.is_file() {
+                return Some(Header {
+                    path: path.canonicalize().unwrap_or(path),
+                    index: Some(i),
+                    system: directory.system,
+                });
+            }
+        }
+        None
+    }
+
+    /// Returns the header name of `#include` operand and whether it is `<...>`.
+    fn header_name(&self, operand: &str, depth: usize) -> Option<(String, bool)> {
+        let operand = operand.trim();
+        let (close, angled) = match operand.chars().next()? {
+            '"' => ('"', false),
+            '<' => ('>', true),
+            _ => {
+                // Computed include such as `#include HEADER`
+                match self.macros.get(identifier(operand)) {
+                    Some(Macro::Object(body)) if depth < MAX_DEPTH => {
+                        return self.header_name(body, depth + 1);
+                    }
+                    _ => return None,
+                }
+            }
+        };
+        let end = operand[1..].find(close)?;
+        Some((String::from(&operand[1..end + 1]), angled))
+    }
+
+    /// Scans `header` unless it has been scanned already.
+    fn enter(&mut self, header: Header, depth: usize) -> Option<Include> {
+        if self.user_headers_only && header.system {
+            return None;
+        }
+        if !self.visited.insert(header.path.clone()) {
+            return None;
+        }
+        let mut include = Include::new(header.path.clone());
+        if depth < MAX_DEPTH {
+            include.includes = self.scan_file(&header, depth + 1).unwrap_or_else(|why| {
+                info!("Failed to read {:?}: {}", header.path, why);
+                Vec::new()
+            });
+        }
+        Some(include)
+    }
+
+    fn scan_file(&mut self, header: &Header, depth: usize) -> Result<Vec<Include>> {
+        let text = fs::read(&header.path)?;
+        let text = String::from_utf8_lossy(&text);
+        let mut includes = Vec::new();
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for line in logical_lines(&text) {
+            let directive = match line.trim_start().strip_prefix('#') {
+                Some(directive) => directive.trim_start(),
+                None => continue,
+            };
+            let name = identifier(directive);
+            let rest = directive[name.len()..].trim();
+            let active = conditionals.last().is_none_or(|v| v.active);
+            match name {
+                "if" | "ifdef" | "ifndef" => {
+                    let value = if active {
+                        self.condition(name, rest, header)
+                    } else {
+                        Some(false)
+                    };
+                    conditionals.push(Conditional {
+                        parent: active,
+                        active: value != Some(false),
+                        taken: value == Some(true),
+                    });
+                }
+                "elif" | "elifdef" | "elifndef" => {
+                    if let Some(conditional) = conditionals.last_mut() {
+                        if conditional.taken || !conditional.parent {
+                            conditional.active = false;
+                        } else {
+                            let value = self.condition(name, rest, header);
+                            conditional.active = value != Some(false);
+                            conditional.taken = value == Some(true);
+                        }
+                    }
+                }
+                "else" => {
+                    if let Some(conditional) = conditionals.last_mut() {
+                        conditional.active = conditional.parent && !conditional.taken;
+                    }
+                }
+                "endif" => {
+                    conditionals.pop();
+                }
+                _ if !active => {}
+                "define" if self.mode == ScanMode::Evaluate => self.define(rest),
+                "undef" if self.mode == ScanMode::Evaluate => self.undefine(rest),
+                "include" | "include_next" | "import" => {
+                    let (file, angled) = match self.header_name(rest, 0) {
+                        Some(v) => v,
+                        None => {
+                            info!("Skip computed include in {:?}: {}", header.path, rest);
+                            continue;
+                        }
+                    };
+                    let next = name == "include_next";
+                    match self.resolve(&file, angled, header, next) {
+                        Some(found) => includes.extend(self.enter(found, depth)),
+                        None => info!(
+                            "Header not found: {:?} included from {:?}",
+                            file, header.path
+                        ),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(includes)
+    }
+
+    /// Evaluates the condition of `#if`, `#ifdef`, `#elif` and so on. `None` if unknown.
+    fn condition(&self, directive: &str, operand: &str, header: &Header) -> Option<bool> {
+        match directive {
+            "ifdef" | "elifdef" => self.defined(identifier(operand)),
+            "ifndef" | "elifndef" => self.defined(identifier(operand)).map(|v| !v),
+            _ => self.evaluate(operand, header).map(|v| v != 0),
+        }
+    }
+
+    fn evaluate(&self, expression: &str, header: &Header) -> Option<i64> {
+        if self.mode == ScanMode::Superset {
+            return None;
+        }
+        let mut tokens = Vec::new();
+        self.expand(&tokenize(expression), header, &mut Vec::new(), &mut tokens);
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            error: false,
+        };
+        let value = parser.conditional();
+        if parser.error || parser.position != tokens.len() {
+            trace!("evaluate: failed to evaluate: {}", expression);
+            return None;
+        }
+        trace!("evaluate: {} => {:?}", expression, value);
+        value
+    }
+
+    /// Replaces macros, `defined` and `__has_include` in `tokens` with their values.
+    fn expand(
+        &self,
+        tokens: &[Token],
+        header: &Header,
+        hidden: &mut Vec<String>,
+        result: &mut Vec<Token>,
+    ) {
+        let mut i = 0;
+        while i < tokens.len() {
+            let name = match tokens[i] {
+                Token::Identifier(ref name) => name.as_str(),
+                ref token => {
+                    result.push(token.clone());
+                    i += 1;
+                    continue;
+                }
+            };
+            i += 1;
+            let value = match name {
+                "defined" => {
+                    let operand = match (tokens.get(i), tokens.get(i + 1), tokens.get(i + 2)) {
+                        (Some(Token::Identifier(name)), _, _) => {
+                            i += 1;
+                            name
+                        }
+                        (
+                            Some(Token::Punctuator("(")),
+                            Some(Token::Identifier(name)),
+                            Some(Token::Punctuator(")")),
+                        ) => {
+                            i += 3;
+                            name
+                        }
+                        _ => {
+                            result.push(Token::Other);
+                            continue;
+                        }
+                    };
+                    self.defined(operand).map(i64::from)
+                }
+                "__has_include" | "__has_include_next" => {
+                    match (tokens.get(i), tokens.get(i + 1), tokens.get(i + 2)) {
+                        (
+                            Some(Token::Punctuator("(")),
+                            Some(Token::HeaderName(file, angled)),
+                            Some(Token::Punctuator(")")),
+                        ) => {
+                            i += 3;
+                            let next = name == "__has_include_next";
+                            Some(i64::from(
+                                self.resolve(file, *angled, header, next).is_some(),
+                            ))
+                        }
+                        _ => None,
+                    }
+                }
+                "true" => Some(1),
+                "false" => Some(0),
+                name if hidden.iter().any(|v| v == name) => Some(0),
+                name => match self.macros.get(name) {
+                    Some(Macro::Object(body)) => {
+                        hidden.push(String::from(name));
+                        self.expand(&tokenize(body), header, hidden, result);
+                        hidden.pop();
+                        continue;
+                    }
+                    Some(Macro::Opaque) => None,
+                    None if self.undefined.contains(name) || !is_reserved(name) => Some(0),
+                    None => None,
+                },
+            };
+            if value.is_none() && tokens.get(i) == Some(&Token::Punctuator("(")) {
+                // Skip arguments of unknown function-like macros such as `__has_feature(x)`
+                let mut nest = 0;
+                while let Some(token) = tokens.get(i) {
+                    i += 1;
+                    match token {
+                        Token::Punctuator("(") => nest += 1,
+                        Token::Punctuator(")") => nest -= 1,
+                        _ => {}
+                    }
+                    if nest == 0 {
+                        break;
+                    }
+                }
+            }
+            result.push(Token::Number(value));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Identifier(String),
+    /// Integer. `None` if unknown.
+    Number(Option<i64>),
+    Punctuator(&'static str),
+    /// Operand of `__has_include`, and whether it is `<...>`.
+    HeaderName(String, bool),
+    /// Token not allowed in `#if`, such as string literals.
+    Other,
+}
+
+/// Punctuators in `#if`. Longer ones come first.
+const PUNCTUATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "(", ")", "!", "~", "+", "-", "*", "/", "%",
+    "<", ">", "&", "|", "^", "?", ":", ",",
+];
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text: String = text.chars().filter(|c| *c != '\'').collect();
+    let text = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let (digits, radix) = if let Some(v) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        (v, 16)
+    } else if let Some(v) = text.strip_prefix("0b").or(text.strip_prefix("0B")) {
+        (v, 2)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (&text[1..], 8)
+    } else {
+        (text, 10)
+    };
+    u64::from_str_radix(digits, radix).ok().map(|v| v as i64)
+}
+
+/// Splits `__has_include` operand such as `(<stdio.h>)` from `text`.
+fn header_argument(text: &str) -> Option<(Token, &str)> {
+    let text = text.trim_start().strip_prefix('(')?.trim_start();
+    let (close, angled) = match text.chars().next()? {
+        '"' => ('"', false),
+        '<' => ('>', true),
+        _ => return None,
+    };
+    let end = text[1..].find(close)? + 1;
+    let name = Token::HeaderName(String::from(&text[1..end]), angled);
+    Some((name, &text[end + 1..]))
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        if c.is_ascii_alphabetic() || c == '_' {
+            let name = identifier(rest);
+            rest = &rest[name.len()..];
+            tokens.push(Token::Identifier(String::from(name)));
+            if name == "__has_include" || name == "__has_include_next" {
+                if let Some((header, after)) = header_argument(rest) {
+                    tokens.push(Token::Punctuator("("));
+                    tokens.push(header);
+                    rest = after;
+                }
+            }
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '\'' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..end])));
+            rest = &rest[end..];
+        } else if c == '\'' {
+            // Character literal. Only plain characters are supported
+            let mut chars = rest[1..].chars();
+            let value = match (chars.next(), chars.next()) {
+                (Some(c), Some('\'')) if c != '\\' => Some(c as i64),
+                _ => None,
+            };
+            tokens.push(Token::Number(value));
+            let end = rest[1..].find('\'').map_or(rest.len(), |v| v + 2);
+            rest = &rest[end..];
+        } else if let Some(punctuator) = PUNCTUATORS.iter().find(|v| rest.starts_with(*v)) {
+            tokens.push(Token::Punctuator(punctuator));
+            rest = &rest[punctuator.len()..];
+        } else {
+            tokens.push(Token::Other);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    tokens
+}
+
+/// Binary operators from the lowest precedence.
+const BINARY_OPERATORS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Applies binary operator to possibly unknown operands.
+fn apply(operator: &str, lhs: Option<i64>, rhs: Option<i64>) -> Option<i64> {
+    let is_true = |v: Option<i64>| matches!(v, Some(v) if v != 0);
+    match operator {
+        "&&" if lhs == Some(0) || rhs == Some(0) => return Some(0),
+        "&&" if is_true(lhs) && is_true(rhs) => return Some(1),
+        "||" if is_true(lhs) || is_true(rhs) => return Some(1),
+        "||" if lhs == Some(0) && rhs == Some(0) => return Some(0),
+        "&&" | "||" => return None,
+        _ => {}
+    }
+    let (a, b) = (lhs?, rhs?);
+    Some(match operator {
+        "|" => a | b,
+        "^" => a ^ b,
+        "&" => a & b,
+        "==" => i64::from(a == b),
+        "!=" => i64::from(a != b),
+        "<" => i64::from(a < b),
+        ">" => i64::from(a > b),
+        "<=" => i64::from(a <= b),
+        ">=" => i64::from(a >= b),
+        "<<" => a.wrapping_shl(b as u32),
+        ">>" => a.wrapping_shr(b as u32),
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" => a.checked_div(b)?,
+        "%" => a.checked_rem(b)?,
+        _ => unreachable!(),
+    })
+}
+
+/// Evaluates expanded `#if` expression. Values are `None` if unknown.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    error: bool,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, punctuator: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Punctuator(v)) if *v == punctuator => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn conditional(&mut self) -> Option<i64> {
+        let condition = self.binary(0);
+        if !self.eat("?") {
+            return condition;
+        }
+        let then = self.conditional();
+        if !self.eat(":") {
+            self.error = true;
+            return None;
+        }
+        let otherwise = self.conditional();
+        match condition {
+            Some(0) => otherwise,
+            Some(_) => then,
+            None if then == otherwise => then,
+            None => None,
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Option<i64> {
+        let operators = match BINARY_OPERATORS.get(level) {
+            Some(operators) => operators,
+            None => return self.unary(),
+        };
+        let mut lhs = self.binary(level + 1);
+        while let Some(operator) = operators.iter().find(|v| self.eat(v)) {
+            let rhs = self.binary(level + 1);
+            lhs = apply(operator, lhs, rhs);
+        }
+        lhs
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        if self.eat("!") {
+            self.unary().map(|v| i64::from(v == 0))
+        } else if self.eat("~") {
+            self.unary().map(|v| !v)
+        } else if self.eat("-") {
+            self.unary().map(i64::wrapping_neg)
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("(") {
+            let value = self.conditional();
+            if !self.eat(")") {
+                self.error = true;
+            }
+            value
+        } else if let Some(Token::Number(value)) = self.tokens.get(self.position) {
+            self.position += 1;
+            *value
+        } else {
+            self.error = true;
+            None
+        }
+    }
+}
+
+/// Splits `text` into logical lines. Lines continued with backslash are joined and comments are
+/// replaced with a space.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut quote = None;
+    let mut block_comment = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && matches!(chars.peek(), Some('\n' | '\r')) {
+            if chars.next() == Some('\r') && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            continue;
+        }
+        if block_comment {
+            if c == '*' && chars.peek() == Some(&'/') {
+                chars.next();
+                block_comment = false;
+                line.push(' ');
+            }
+            continue;
+        }
+        match c {
+            '\n' => {
+                lines.push(mem::take(&mut line));
+                quote = None;
+            }
+            '\\' if quote.is_some() => {
+                line.push(c);
+                line.extend(chars.next_if(|v| *v != '\n'));
+            }
+            '"' | '\'' if quote.is_none() => {
+                quote = Some(c);
+                line.push(c);
+            }
+            c if Some(c) == quote => {
+                quote = None;
+                line.push(c);
+            }
+            '/' if quote.is_none() && chars.peek() == Some(&'*') => {
+                chars.next();
+                block_comment = true;
+            }
+            '/' if quote.is_none() && chars.peek() == Some(&'/') => {
+                while chars.next_if(|v| *v != '\n').is_some() {}
+            }
+            c => line.push(c),
+        }
+    }
+    lines.push(line);
+    lines
+}
@@ -0,0 +1,230 @@
+use log::{trace, warn};
+use std::collections::HashMap;
+use std::env;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::arguments::remove_launchers;
+use crate::compile_command::CompileCommand;
+use crate::compiler::CompilerFamily;
+use crate::error::{Error, Result};
+
+/// System include directories assumed when the compiler cannot tell.
+pub const FALLBACK_SYSTEM_DIRECTORIES: &[&str] =
+    &["/usr/include", "/usr/lib", "/usr/local/include"];
+
+/// Options that change the search list of the compiler, with the number of values they take.
+const SEARCH_LIST_OPTIONS: &[(&str, usize)] = &[
+    ("--sysroot", 1),
+    ("-isysroot", 1),
+    ("-target", 1),
+    ("--target", 1),
+    ("--gcc-toolchain", 1),
+    // libc++ or libstdc++
+    ("-stdlib=", 1),
+    ("--stdlib", 1),
+    ("-nostdinc", 0),
+    ("-nostdinc++", 0),
+    ("-nostdlibinc", 0),
+    ("-nobuiltininc", 0),
+    ("-m32", 0),
+    ("-m64", 0),
+];
+
+/// Options of [`SEARCH_LIST_OPTIONS`] whose value is a path.
+const SEARCH_LIST_PATH_OPTIONS: &[&str] = &["--sysroot", "-isysroot", "--gcc-toolchain"];
+
+/// Options whose value is a system include directory.
+const SYSTEM_DIRECTORY_OPTIONS: &[&str] = &[
+    "-isystem",
+    "-idirafter",
+    "-isysroot",
+    "--sysroot",
+    "/imsvc",
+    "-imsvc",
+    "/external:I",
+    "-external:I",
+];
+
+/// Returns `(name, value)` if `args[i]` is `name value`, `name=value` or `namevalue`.
+pub(crate) fn option_value<'a>(
+    args: &'a [String],
+    i: usize,
+    name: &str,
+) -> Option<(&'a str, usize)> {
+    let arg = args[i].as_str();
+    if arg == name {
+        return args.get(i + 1).map(|v| (v.as_str(), 2));
+    }
+    let value = arg.strip_prefix(name)?;
+    // `--sysroot=dir`, `-isystemdir`
+    let value = value.strip_prefix('=').unwrap_or(value);
+    if name.starts_with("--") && !arg[name.len()..].starts_with('=') {
+        return None;
+    }
+    Some((value, 1))
+}
+
+/// Parses `#include <...>` search list in the output of `-E -v`.
+///
+/// The list may be empty, e.g. with `-nostdinc`, but the output must have it.
+pub fn parse_search_list<R: BufRead>(output: R) -> Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    let mut in_list = false;
+    for line in output.lines() {
+        let line = line?;
+        if line.starts_with("#include <...> search starts here:") {
+            in_list = true;
+        } else if line.starts_with("End of search list.") {
+            break;
+        } else if in_list {
+            let path = line.trim().trim_end_matches(" (framework directory)");
+            result.push(PathBuf::from(path));
+        }
+    }
+    if !in_list {
+        return Err(Error::SearchListNotFoundError);
+    }
+    Ok(result)
+}
+
+pub(crate) fn language(args: &[String], file: &Path) -> &'static str {
+    if let Some(i) = args.iter().rposition(|v| v == "-x") {
+        if let Some("c") = args.get(i + 1).map(String::as_str) {
+            return "c";
+        }
+        return "c++";
+    }
+    match file.extension().and_then(|v| v.to_str()) {
+        Some("c") => "c",
+        _ => "c++",
+    }
+}
+
+/// Query to the compiler, and the directory it runs in if the query has relative paths.
+type SearchListKey = (Vec<String>, Option<PathBuf>);
+
+/// Finds system include directories of translation units.
+///
+/// Search lists of compilers are cached by compiler, language and options that affect them.
+#[derive(Debug, Default)]
+pub struct SystemDirectories {
+    prefixes: Vec<PathBuf>,
+    offline: bool,
+    cache: Mutex<HashMap<SearchListKey, Vec<PathBuf>>>,
+}
+
+impl SystemDirectories {
+    /// `prefixes` are regarded as system directories in addition to what compilers tell.
+    ///
+    /// Relative prefixes are resolved against the current directory.
+    pub fn new(prefixes: Vec<PathBuf>) -> Self {
+        Self {
+            prefixes: prefixes
+                .into_iter()
+                .map(|v| v.canonicalize().unwrap_or(v))
+                .collect(),
+            offline: false,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Never runs compilers, and assumes [`FALLBACK_SYSTEM_DIRECTORIES`] as their search lists.
+    pub fn offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    fn search_list(&self, command: &CompileCommand, args: &[String]) -> Vec<PathBuf> {
+        if CompilerFamily::detect(args) == CompilerFamily::Msvc {
+            // cl searches `INCLUDE` environment variable for system headers
+            return env::var_os("INCLUDE")
+                .map(|v| env::split_paths(&v).collect())
+                .unwrap_or_default();
+        }
+        if self.offline {
+            return FALLBACK_SYSTEM_DIRECTORIES
+                .iter()
+                .map(PathBuf::from)
+                .collect();
+        }
+        let mut query = vec![args[0].clone()];
+        // `./toolchain/bin/gcc`, but not `gcc` searched in `PATH`
+        let compiler = Path::new(&args[0]);
+        let mut relative = compiler.is_relative() && compiler.components().count() > 1;
+        let mut i = 1;
+        while i < args.len() {
+            let mut consumed = 1;
+            for (name, values) in SEARCH_LIST_OPTIONS {
+                if *values == 0 && args[i] == *name {
+                    query.push(args[i].clone());
+                } else if *values == 1 {
+                    if let Some((value, n)) = option_value(args, i, name) {
+                        query.extend(args[i..i + n].iter().cloned());
+                        consumed = n;
+                        relative |= SEARCH_LIST_PATH_OPTIONS.contains(name)
+                            && Path::new(value).is_relative();
+                    }
+                }
+            }
+            i += consumed;
+        }
+        query.push(String::from("-x"));
+        query.push(String::from(language(args, &command.file)));
+
+        let key = (query, relative.then(|| command.directory.clone()));
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            return cached.clone();
+        }
+        let query = &key.0;
+        let result = Command::new(&query[0])
+            .args(&query[1..])
+            .args(["-E", "-v", "/dev/null", "-o", "/dev/null"])
+            .current_dir(&command.directory)
+            .output()
+            .map_err(From::from)
+            .and_then(|output| parse_search_list(output.stderr.as_slice()));
+        let list = match result {
+            Ok(list) => list,
+            Err(_) => {
+                warn!(
+                    "Failed to get search list of the compiler. Assume {:?}: {:?}",
+                    FALLBACK_SYSTEM_DIRECTORIES, query
+                );
+                FALLBACK_SYSTEM_DIRECTORIES
+                    .iter()
+                    .map(PathBuf::from)
+                    .collect()
+            }
+        };
+        trace!("search_list: {:?} => {:?}", query, list);
+        self.cache.lock().unwrap().insert(key, list.clone());
+        list
+    }
+
+    /// Returns canonicalized system include directories of `command`.
+    pub fn get(&self, command: &CompileCommand) -> Result<Vec<PathBuf>> {
+        let args = remove_launchers(&command.arguments()?, &[]);
+        let mut result = self.search_list(command, &args);
+        for i in 1..args.len() {
+            for name in SYSTEM_DIRECTORY_OPTIONS {
+                if let Some((value, _)) = option_value(&args, i, name) {
+                    result.push(PathBuf::from(value));
+                }
+            }
+        }
+        result.extend(self.prefixes.iter().cloned());
+
+        let mut canonicalized = Vec::new();
+        for path in result {
+            let path = command.directory.join(path);
+            let path = path.canonicalize().unwrap_or(path);
+            if !canonicalized.contains(&path) {
+                canonicalized.push(path);
+            }
+        }
+        Ok(canonicalized)
+    }
+}
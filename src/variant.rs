@@ -0,0 +1,206 @@
+//! Multiple compile commands for the same file, e.g. built for several targets with different
+//! `-D`s.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::compile_command::CompileCommand;
+use crate::dependency::TranslationUnit;
+use crate::include_tree::Include;
+
+/// How to report translation units compiled more than once.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VariantMode {
+    /// Dependencies of any variant.
+    #[default]
+    Union,
+    /// Dependencies common to all variants.
+    Intersection,
+    /// Each variant separately.
+    Each,
+}
+
+/// Identifies the file regardless of how the directory and the file are written.
+fn key(directory: &Path, file: &Path) -> PathBuf {
+    let directory = directory
+        .canonicalize()
+        .unwrap_or_else(|_| directory.to_path_buf());
+    let path = directory.join(file);
+    path.canonicalize().unwrap_or(path)
+}
+
+/// Returns labels that tell apart commands for the same file. `None` for files compiled once.
+///
+/// Commands are labeled by `output` if all of them have distinct ones, otherwise by arguments
+/// the other commands for the file do not have.
+pub(crate) fn variant_labels(commands: &[CompileCommand]) -> Vec<Option<String>> {
+    let mut groups: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (i, command) in commands.iter().enumerate() {
+        groups
+            .entry(key(&command.directory, &command.file))
+            .or_default()
+            .push(i);
+    }
+
+    let mut labels = vec![None; commands.len()];
+    for group in groups.values().filter(|v| v.len() > 1) {
+        let mut outputs: Vec<_> = group
+            .iter()
+            .filter_map(|i| commands[*i].output.as_ref())
+            .collect();
+        outputs.sort();
+        outputs.dedup();
+        if outputs.len() == group.len() {
+            for i in group.iter() {
+                labels[*i] = commands[*i]
+                    .output
+                    .as_ref()
+                    .map(|v| v.display().to_string());
+            }
+            continue;
+        }
+
+        let args: Vec<Vec<String>> = group
+            .iter()
+            .map(|i| commands[*i].arguments().unwrap_or_default())
+            .collect();
+        let mut group_labels: Vec<String> = args
+            .iter()
+            .map(|own| {
+                own.iter()
+                    .skip(1)
+                    .filter(|v| !args.iter().all(|other| other.contains(v)))
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        for k in 0..group_labels.len() {
+            let label = &group_labels[k];
+            if label.is_empty() || group_labels.iter().filter(|v| *v == label).count() > 1 {
+                group_labels[k] = format!("{} #{}", label, k + 1).trim_start().to_string();
+            }
+        }
+        for (i, label) in group.iter().zip(group_labels) {
+            labels[*i] = Some(label);
+        }
+    }
+    labels
+}
+
+/// Labels `units` extracted from `commands` in the same order, as
+/// [`dump_dependencies`](crate::dump_dependencies) does. For results of
+/// [`dump_dependencies_streaming`](crate::dump_dependencies_streaming).
+pub fn label_variants(commands: &[CompileCommand], units: &mut [TranslationUnit]) {
+    for (unit, label) in units.iter_mut().zip(variant_labels(commands)) {
+        unit.variant = label;
+    }
+}
+
+/// Removes nodes rejected by `keep`, promoting their children.
+fn retain_includes<F>(includes: Vec<Include>, keep: &F) -> Vec<Include>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut result = Vec::new();
+    for mut include in includes {
+        let includes = retain_includes(std::mem::take(&mut include.includes), keep);
+        if keep(&include.path) {
+            include.includes = includes;
+            result.push(include);
+        } else {
+            result.extend(includes);
+        }
+    }
+    result
+}
+
+fn merge(mut variants: Vec<TranslationUnit>, mode: VariantMode) -> TranslationUnit {
+    // Variants that failed do not contribute unless all of them failed
+    if variants.iter().any(|v| v.error.is_none()) {
+        variants.retain(|v| v.error.is_none());
+    }
+    let mut variants = variants.into_iter();
+    let mut unit = variants.next().expect("No variant");
+    unit.variant = None;
+    for variant in variants {
+        match mode {
+            VariantMode::Union => {
+                for path in variant.dependencies {
+                    if !unit.dependencies.contains(&path) {
+                        unit.dependencies.push(path);
+                    }
+                }
+                if let (Some(includes), Some(others)) = (&mut unit.includes, variant.includes) {
+                    for include in others {
+                        if !includes.contains(&include) {
+                            includes.push(include);
+                        }
+                    }
+                }
+                if let (Some(modules), Some(others)) = (&mut unit.modules, variant.modules) {
+                    for name in others.provides {
+                        if !modules.provides.contains(&name) {
+                            modules.provides.push(name);
+                        }
+                    }
+                    for name in others.requires {
+                        if !modules.requires.contains(&name) {
+                            modules.requires.push(name);
+                        }
+                    }
+                }
+            }
+            VariantMode::Intersection => {
+                unit.dependencies
+                    .retain(|v| variant.dependencies.contains(v));
+                if let Some(modules) = unit.modules.as_mut() {
+                    let others = variant.modules.unwrap_or_default();
+                    modules.provides.retain(|v| others.provides.contains(v));
+                    modules.requires.retain(|v| others.requires.contains(v));
+                }
+            }
+            VariantMode::Each => unreachable!(),
+        }
+        for directory in variant.system_directories {
+            if !unit.system_directories.contains(&directory) {
+                unit.system_directories.push(directory);
+            }
+        }
+        if unit.output != variant.output {
+            unit.output = None;
+        }
+    }
+    if mode == VariantMode::Intersection {
+        if let Some(includes) = unit.includes.take() {
+            let dependencies = &unit.dependencies;
+            unit.includes = Some(retain_includes(includes, &|path| {
+                dependencies.iter().any(|v| v == path)
+            }));
+        }
+    }
+    unit
+}
+
+/// Merges variants of each translation unit according to `mode`. Order of first appearance is
+/// kept.
+///
+/// `output` of a merged unit is kept only if all variants have the same one.
+pub fn merge_variants(units: Vec<TranslationUnit>, mode: VariantMode) -> Vec<TranslationUnit> {
+    if mode == VariantMode::Each {
+        return units;
+    }
+    let mut order = Vec::new();
+    let mut groups: HashMap<PathBuf, Vec<TranslationUnit>> = HashMap::new();
+    for unit in units {
+        let key = key(&unit.directory, &unit.file);
+        if !groups.contains_key(&key) {
+            order.push(key.clone());
+        }
+        groups.entry(key).or_default().push(unit);
+    }
+    order
+        .into_iter()
+        .filter_map(|key| groups.remove(&key))
+        .map(|variants| merge(variants, mode))
+        .collect()
+}
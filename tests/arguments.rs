@@ -0,0 +1,282 @@
+use dump_dependency::arguments::{
+    depfile_argument, output_argument, remove_dependency_flags, remove_launchers,
+    remove_msvc_output_flags, remove_output_flags,
+};
+
+fn args(command: &str) -> Vec<String> {
+    shell_words::split(command).unwrap()
+}
+
+#[test]
+fn cmake_makefiles() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "/usr/bin/cc -DFOO -I/src/include -O2 -MD -MT CMakeFiles/app.dir/main.c.o \
+             -MF CMakeFiles/app.dir/main.c.o.d -o CMakeFiles/app.dir/main.c.o -c /src/main.c"
+        )),
+        args("/usr/bin/cc -DFOO -I/src/include -O2 -o CMakeFiles/app.dir/main.c.o -c /src/main.c")
+    );
+}
+
+#[test]
+fn cmake_ninja() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "/usr/bin/c++ -I/src/include -std=gnu++17 -MD -MT CMakeFiles/app.dir/main.cpp.o \
+             -MF CMakeFiles/app.dir/main.cpp.o.d -o CMakeFiles/app.dir/main.cpp.o -c /src/main.cpp"
+        )),
+        args("/usr/bin/c++ -I/src/include -std=gnu++17 -o CMakeFiles/app.dir/main.cpp.o -c /src/main.cpp")
+    );
+}
+
+#[test]
+fn meson() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "cc -Iapp.p -I. -I.. -fdiagnostics-color=always -D_FILE_OFFSET_BITS=64 -Wall \
+             -MD -MQ app.p/main.c.o -MF app.p/main.c.o.d -o app.p/main.c.o -c ../main.c"
+        )),
+        args(
+            "cc -Iapp.p -I. -I.. -fdiagnostics-color=always -D_FILE_OFFSET_BITS=64 -Wall \
+             -o app.p/main.c.o -c ../main.c"
+        )
+    );
+}
+
+#[test]
+fn autotools() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "gcc -DHAVE_CONFIG_H -I. -I..  -g -O2 -MT foo.o -MD -MP -MF .deps/foo.Tpo -c -o foo.o foo.c"
+        )),
+        args("gcc -DHAVE_CONFIG_H -I. -I.. -g -O2 -c -o foo.o foo.c")
+    );
+}
+
+#[test]
+fn libtool() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "gcc -DHAVE_CONFIG_H -I. -g -O2 -MT libfoo_la-foo.lo -MD -MP -MF .deps/libfoo_la-foo.Tpo \
+             -c foo.c -fPIC -DPIC -o .libs/libfoo_la-foo.o"
+        )),
+        args("gcc -DHAVE_CONFIG_H -I. -g -O2 -c foo.c -fPIC -DPIC -o .libs/libfoo_la-foo.o")
+    );
+}
+
+#[test]
+fn kbuild_preprocessor_flags() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "gcc -Wp,-MMD,drivers/foo/.bar.o.d -nostdinc -Wp,-MD,x.d,-DFOO -c -o drivers/foo/bar.o drivers/foo/bar.c"
+        )),
+        args("gcc -nostdinc -Wp,-DFOO -c -o drivers/foo/bar.o drivers/foo/bar.c")
+    );
+}
+
+#[test]
+fn glued_values() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "clang -MMD -MFfoo.d -MTfoo.o -MQ'$(objdir)/foo.o' -MJfoo.json -save-temps=obj -c foo.c"
+        )),
+        args("clang -c foo.c")
+    );
+}
+
+#[test]
+fn existing_make_flags() {
+    assert_eq!(
+        remove_dependency_flags(&args("gcc -M -MG -MM -c foo.c")),
+        args("gcc -c foo.c")
+    );
+}
+
+#[test]
+fn gcc_module_scan() {
+    assert_eq!(
+        remove_dependency_flags(&args(
+            "g++ -std=c++20 -fmodules-ts -E -fdeps-format=p1689r5 -fdeps-file=foo.ddi \
+             -fdeps-target=foo.o -c foo.cppm"
+        )),
+        args("g++ -std=c++20 -fmodules-ts -E -c foo.cppm")
+    );
+}
+
+#[test]
+fn keeps_compiler() {
+    assert_eq!(remove_dependency_flags(&args("-MD")), args("-MD"));
+}
+
+#[test]
+fn output_separate() {
+    assert_eq!(
+        remove_output_flags(&args("cc -c foo.c -o foo.o -Wall")),
+        args("cc -c foo.c -Wall")
+    );
+}
+
+#[test]
+fn output_glued() {
+    assert_eq!(
+        remove_output_flags(&args("cc -c foo.c -ofoo.o")),
+        args("cc -c foo.c")
+    );
+}
+
+#[test]
+fn output_equals() {
+    assert_eq!(
+        remove_output_flags(&args("cc -c foo.c -o=foo.o")),
+        args("cc -c foo.c")
+    );
+}
+
+#[test]
+fn output_long() {
+    assert_eq!(
+        remove_output_flags(&args("cc -c foo.c --output foo.o --output=bar.o")),
+        args("cc -c foo.c")
+    );
+}
+
+#[test]
+fn output_repeated() {
+    assert_eq!(
+        remove_output_flags(&args("cc -o a.o -c foo.c -o b.o")),
+        args("cc -c foo.c")
+    );
+}
+
+#[test]
+fn output_last_token() {
+    assert_eq!(
+        remove_output_flags(&args("cc -c foo.c -o")),
+        args("cc -c foo.c")
+    );
+}
+
+#[test]
+fn output_msvc() {
+    assert_eq!(
+        remove_msvc_output_flags(&args(
+            "cl.exe /nologo /c foo.c /Fofoo.obj /Fo:bar.obj /Fo: baz.obj -Foqux.obj /Fefoo.exe"
+        )),
+        args("cl.exe /nologo /c foo.c")
+    );
+}
+
+#[test]
+fn output_gcc_keeps_msvc_lookalikes() {
+    assert_eq!(
+        remove_output_flags(&args("clang -Fexternal/fw -c /Federation/a.c -o a.o")),
+        args("clang -Fexternal/fw -c /Federation/a.c")
+    );
+    assert_eq!(
+        remove_output_flags(&args("cc -c /Foo/a.c -o a.o")),
+        args("cc -c /Foo/a.c")
+    );
+    assert_eq!(
+        output_argument(&args("cc -c /Foo/a.c -o a.o")),
+        Some(String::from("a.o"))
+    );
+    assert_eq!(output_argument(&args("clang -c /Fe/a.c")), None);
+}
+
+#[test]
+fn output_msvc_keeps_gcc_lookalikes() {
+    assert_eq!(
+        remove_msvc_output_flags(&args("clang-cl /c -ofast.c /Fo:a.obj")),
+        args("clang-cl /c -ofast.c")
+    );
+}
+
+#[test]
+fn output_lookalikes() {
+    assert_eq!(
+        remove_output_flags(&args("clang -objcmt-migrate-literals -c foo.m -O2")),
+        args("clang -objcmt-migrate-literals -c foo.m -O2")
+    );
+}
+
+#[test]
+fn output_keeps_compiler() {
+    assert_eq!(remove_output_flags(&args("-o")), args("-o"));
+}
+
+#[test]
+fn launchers() {
+    assert_eq!(
+        remove_launchers(&args("ccache gcc -c foo.c"), &[]),
+        args("gcc -c foo.c")
+    );
+    assert_eq!(
+        remove_launchers(&args("/usr/bin/sccache /usr/bin/clang++ -c foo.cc"), &[]),
+        args("/usr/bin/clang++ -c foo.cc")
+    );
+    assert_eq!(
+        remove_launchers(&args("ccache distcc gcc -c foo.c"), &[]),
+        args("gcc -c foo.c")
+    );
+    assert_eq!(
+        remove_launchers(&args("icecc.exe cl.exe /c foo.c"), &[]),
+        args("cl.exe /c foo.c")
+    );
+}
+
+#[test]
+fn custom_launchers() {
+    let launchers = vec![String::from("/opt/wrap/cc-wrapper"), String::from("goma")];
+    assert_eq!(
+        remove_launchers(&args("/opt/wrap/cc-wrapper gcc -c foo.c"), &launchers),
+        args("gcc -c foo.c")
+    );
+    assert_eq!(
+        remove_launchers(&args("/usr/local/bin/goma gcc -c foo.c"), &launchers),
+        args("gcc -c foo.c")
+    );
+    assert_eq!(
+        remove_launchers(&args("gcc -c foo.c"), &launchers),
+        args("gcc -c foo.c")
+    );
+}
+
+#[test]
+fn launcher_alone() {
+    assert_eq!(remove_launchers(&args("ccache"), &[]), args("ccache"));
+}
+
+#[test]
+fn output_argument_forms() {
+    assert_eq!(
+        output_argument(&args("cc -o a.o -c foo.c -o b.o")),
+        Some(String::from("b.o"))
+    );
+    assert_eq!(
+        output_argument(&args("cc -c foo.c -ofoo.o")),
+        Some(String::from("foo.o"))
+    );
+    assert_eq!(
+        output_argument(&args("cl.exe /c foo.c /Fo:foo.obj")),
+        Some(String::from("foo.obj"))
+    );
+    assert_eq!(output_argument(&args("cc -objc -c foo.m")), None);
+    assert_eq!(output_argument(&args("cc -c foo.c -o")), None);
+}
+
+#[test]
+fn depfile_argument_forms() {
+    assert_eq!(
+        depfile_argument(&args("cc -MD -MF CMakeFiles/app.dir/main.c.o.d -c main.c")),
+        Some(String::from("CMakeFiles/app.dir/main.c.o.d"))
+    );
+    assert_eq!(
+        depfile_argument(&args("cc -MMD -MFfoo.d -c foo.c")),
+        Some(String::from("foo.d"))
+    );
+    assert_eq!(
+        depfile_argument(&args("gcc -Wp,-MMD,drivers/foo/.bar.o.d -c bar.c")),
+        Some(String::from("drivers/foo/.bar.o.d"))
+    );
+    assert_eq!(depfile_argument(&args("cc -MD -c foo.c")), None);
+}
@@ -0,0 +1,112 @@
+use dump_dependency::{depfile_candidates, read_build_depfile, CompileCommand, Error};
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Creates a build tree with `src/main.c`, `src/main.h` and an empty `build`.
+fn tree(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!(
+        "dump-dependency-test-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("build/CMakeFiles/app.dir")).unwrap();
+    fs::write(root.join("src/main.c"), "#include \"main.h\"\n").unwrap();
+    fs::write(root.join("src/main.h"), "").unwrap();
+    root.canonicalize().unwrap()
+}
+
+fn command(root: &Path, command: &str, output: Option<&str>) -> CompileCommand {
+    CompileCommand {
+        directory: root.join("build"),
+        command: Some(String::from(command)),
+        arguments: None,
+        file: PathBuf::from("../src/main.c"),
+        output: output.map(PathBuf::from),
+    }
+}
+
+fn set_modified(path: &Path, time: SystemTime) {
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+#[test]
+fn candidates() {
+    let root = tree("candidates");
+    let command = command(
+        &root,
+        "cc -MD -MF deps/main.d -c ../src/main.c -o CMakeFiles/app.dir/main.c.o",
+        None,
+    );
+    assert_eq!(
+        depfile_candidates(&command, Some("{stem}.dep")).unwrap(),
+        vec![
+            root.join("build/deps/main.d"),
+            root.join("build/main.dep"),
+            root.join("build/CMakeFiles/app.dir/main.c.o.d"),
+            root.join("build/CMakeFiles/app.dir/main.c.d"),
+        ]
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn cmake_depfile() {
+    let root = tree("cmake");
+    fs::write(
+        root.join("build/CMakeFiles/app.dir/main.c.o.d"),
+        "CMakeFiles/app.dir/main.c.o: ../src/main.c \\\n ../src/main.h\n",
+    )
+    .unwrap();
+    let command = command(
+        &root,
+        "cc -c ../src/main.c",
+        Some("CMakeFiles/app.dir/main.c.o"),
+    );
+    let depfile = read_build_depfile(&command, None).unwrap();
+    assert_eq!(
+        depfile.path,
+        root.join("build/CMakeFiles/app.dir/main.c.o.d")
+    );
+    assert_eq!(
+        depfile.dependencies,
+        vec![root.join("src/main.c"), root.join("src/main.h")]
+    );
+    assert!(!depfile.stale);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn stale_depfile() {
+    let root = tree("stale");
+    fs::write(
+        root.join("build/main.d"),
+        "main.o: ../src/main.c ../src/main.h\n",
+    )
+    .unwrap();
+    let now = SystemTime::now();
+    set_modified(&root.join("build/main.d"), now - Duration::from_secs(60));
+    set_modified(&root.join("src/main.h"), now);
+    let command = command(&root, "cc -c ../src/main.c -o main.o", None);
+    assert!(read_build_depfile(&command, None).unwrap().stale);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn not_found() {
+    let root = tree("not-found");
+    let command = command(&root, "cc -c ../src/main.c -o main.o", None);
+    assert!(matches!(
+        read_build_depfile(&command, None),
+        Err(Error::DepfileNotFoundError)
+    ));
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,91 @@
+use dump_dependency::{parse_build_log, CompileCommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str, directory: &str) -> Vec<CompileCommand> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/build_log")
+        .join(name);
+    let content = fs::read_to_string(&path).unwrap();
+    parse_build_log(&content, Path::new(directory))
+}
+
+fn summary(commands: &[CompileCommand]) -> Vec<(PathBuf, PathBuf, Option<PathBuf>)> {
+    commands
+        .iter()
+        .map(|v| (v.directory.clone(), v.file.clone(), v.output.clone()))
+        .collect()
+}
+
+fn entry(directory: &str, file: &str, output: Option<&str>) -> (PathBuf, PathBuf, Option<PathBuf>) {
+    (
+        PathBuf::from(directory),
+        PathBuf::from(file),
+        output.map(PathBuf::from),
+    )
+}
+
+#[test]
+fn make() {
+    let commands = fixture("make.log", "/tmp");
+    assert_eq!(
+        summary(&commands),
+        vec![
+            entry("/src/project/lib", "util.c", Some("util.o")),
+            entry("/src/project/lib", "dir with space/a b.c", Some("ab.o")),
+            entry("/src/project", "main.cpp", None),
+            entry("/src/project", "a.cc", None),
+            entry("/src/project", "legacy.c", Some(".libs/legacy.o")),
+        ]
+    );
+    assert_eq!(
+        commands[0].arguments().unwrap(),
+        [
+            "gcc",
+            "-Iinclude",
+            "-DNDEBUG",
+            "-c",
+            "util.c",
+            "-o",
+            "util.o"
+        ]
+    );
+    assert_eq!(
+        commands[3].arguments().unwrap(),
+        [
+            "arm-none-eabi-g++-12",
+            "-std=c++17",
+            "-c",
+            "a.cc",
+            "-Ilib/include"
+        ]
+    );
+}
+
+#[test]
+fn ninja() {
+    let commands = fixture("ninja.log", "/build");
+    assert_eq!(
+        summary(&commands),
+        vec![entry(
+            "/build",
+            "/src/main.cpp",
+            Some("CMakeFiles/app.dir/main.cpp.o")
+        )]
+    );
+}
+
+#[test]
+fn cd_prefix() {
+    let commands = parse_build_log(
+        "cd sub && clang -c x.c; cd /abs && cl /c y.cpp /Foy.obj\ngcc -E z.c\n",
+        Path::new("/build"),
+    );
+    assert_eq!(
+        summary(&commands),
+        vec![
+            entry("/build/sub", "x.c", None),
+            entry("/abs", "y.cpp", Some("y.obj")),
+        ]
+    );
+}
@@ -0,0 +1,165 @@
+use dump_dependency::{
+    dump_dependencies, dump_dependencies_streaming, for_each_compile_command, label_variants,
+    parse_compile_commands, read_compile_commands, Backend, CompileCommand, DumpOptions, Error,
+    ScanMode,
+};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn full_entry() {
+    let entries = parse_compile_commands(
+        r#"[{
+            "directory": "/src/build",
+            "arguments": ["cc", "-c", "../main.c", "-o", "main.o"],
+            "file": "../main.c",
+            "output": "main.o",
+            "unknown": 1
+        }]"#,
+        Path::new("/"),
+    )
+    .unwrap();
+    let command = entries[0].as_ref().unwrap();
+    assert_eq!(command.directory, PathBuf::from("/src/build"));
+    assert_eq!(command.output, Some(PathBuf::from("main.o")));
+    assert_eq!(command.path(), PathBuf::from("/src/build/../main.c"));
+}
+
+#[test]
+fn relative_directory() {
+    let base = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let entries = parse_compile_commands(
+        r#"[{"directory": "build", "command": "cc -c main.c", "file": "main.c"}]"#,
+        base,
+    )
+    .unwrap();
+    let command = entries[0].as_ref().unwrap();
+    assert_eq!(
+        command.directory,
+        base.canonicalize().unwrap().join("build")
+    );
+    assert_eq!(
+        command.path(),
+        base.canonicalize().unwrap().join("build/main.c")
+    );
+}
+
+#[test]
+fn invalid_entries() {
+    let entries = parse_compile_commands(
+        r#"[
+            {"directory": "/src", "command": "cc -c a.c", "file": "a.c"},
+            {"directory": "/src", "command": "cc -c b.c"},
+            {"directory": "/src", "file": "c.c"},
+            {"directory": "/src", "arguments": [], "file": "d.c"},
+            {"directory": "/src", "command": "cc -c 'e.c", "file": "e.c"},
+            {"directory": "/src", "arguments": ["cc", 1], "file": "f.c"},
+            {"directory": "", "command": "cc -c g.c", "file": "g.c"},
+            "h.c",
+            {"directory": "/src", "command": "cc -c i.c", "file": "i.c"}
+        ]"#,
+        Path::new("/"),
+    )
+    .unwrap();
+    assert_eq!(entries.len(), 9);
+    assert!(entries[0].is_ok());
+    assert!(entries[8].is_ok());
+    for (i, entry) in entries.iter().enumerate().skip(1).take(7) {
+        match entry {
+            Err(Error::InvalidEntryError(index, _)) => assert_eq!(*index, i),
+            other => panic!("Unexpected result for entry {}: {:?}", i, other),
+        }
+    }
+}
+
+#[test]
+fn not_array() {
+    assert!(parse_compile_commands(r#"{"directory": "/"}"#, Path::new("/")).is_err());
+    assert!(parse_compile_commands("[", Path::new("/")).is_err());
+}
+
+#[test]
+fn stream() {
+    let content = r#"[
+        {"directory": "/src", "command": "cc -c a.c", "file": "a.c"},
+        {"directory": "/src", "command": "cc -c b.c"},
+        {"directory": "/src", "command": "cc -c c.c", "file": "c.c"},
+        {"directory": "/src", "#;
+    let entries: Vec<_> =
+        read_compile_commands(Cursor::new(content.as_bytes().to_vec()), Path::new("/")).collect();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].as_ref().unwrap().file, PathBuf::from("a.c"));
+    assert!(matches!(entries[1], Err(Error::InvalidEntryError(1, _))));
+    assert_eq!(entries[2].as_ref().unwrap().file, PathBuf::from("c.c"));
+    assert!(matches!(entries[3], Err(Error::SerdeJsonError(_))));
+}
+
+#[test]
+fn stop_stream() {
+    let content = r#"[
+        {"directory": "/src", "command": "cc -c a.c", "file": "a.c"},
+        {"directory": "/src", "command": "cc -c b.c", "file": "b.c"}
+    "#;
+    let mut files = Vec::new();
+    for_each_compile_command(content.as_bytes(), Path::new("/"), |entry| {
+        files.push(entry.unwrap().file);
+        false
+    })
+    .unwrap();
+    assert_eq!(files, vec![PathBuf::from("a.c")]);
+}
+
+#[test]
+fn stream_into_dump_dependencies() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scanner");
+    let entries: Vec<_> = (0..16)
+        .map(|i| {
+            let define = if i % 2 == 0 { "-DFOO" } else { "-UFOO" };
+            serde_json::json!({
+                "directory": directory,
+                "command": format!("cc -nostdinc -Iinclude -Iinclude2 {} -c src/main.c -o {}.o", define, i),
+                "file": "src/main.c",
+            })
+        })
+        .collect();
+    let content = serde_json::to_vec(&entries).unwrap();
+    let options = DumpOptions {
+        backend: Backend::Scanner(ScanMode::Evaluate),
+        ..DumpOptions::default()
+    };
+    let commands: Vec<CompileCommand> =
+        parse_compile_commands(std::str::from_utf8(&content).unwrap(), Path::new("/"))
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+    let expected = dump_dependencies(&commands, &options);
+
+    let stream = read_compile_commands(Cursor::new(content), Path::new("/")).map(Result::unwrap);
+    let mut results = Vec::new();
+    dump_dependencies_streaming(stream, &options, |i, command, unit| {
+        results.push((i, command, unit));
+    });
+    results.sort_by_key(|v| v.0);
+    let (streamed, mut units): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .map(|(_, command, unit)| (command, unit))
+        .unzip();
+    assert_eq!(
+        streamed
+            .iter()
+            .map(|v| v.command.clone())
+            .collect::<Vec<_>>(),
+        commands
+            .iter()
+            .map(|v| v.command.clone())
+            .collect::<Vec<_>>()
+    );
+    assert!(units.iter().all(|v| v.variant.is_none()));
+    label_variants(&streamed, &mut units);
+    assert_eq!(units.len(), expected.len());
+    for (unit, expected) in units.iter().zip(expected.iter()) {
+        assert_eq!(unit.dependencies, expected.dependencies);
+        assert_eq!(unit.variant, expected.variant);
+    }
+}
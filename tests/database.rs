@@ -0,0 +1,117 @@
+use dump_dependency::{
+    discover_databases, find_database, load_database, merge_databases, parse_compile_commands,
+    CompileCommand, Conflict, Error,
+};
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/database")
+        .join(name)
+}
+
+fn commands(content: &str) -> Vec<CompileCommand> {
+    parse_compile_commands(content, Path::new("/"))
+        .unwrap()
+        .into_iter()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn build_directory() {
+    let root = fixture("tree");
+    assert_eq!(
+        find_database(&root).unwrap(),
+        root.join("build/compile_commands.json")
+    );
+    let entries = load_database(&root).unwrap();
+    let command = entries[0].as_ref().unwrap();
+    assert_eq!(command.directory, root.join("build"));
+    assert!(matches!(
+        find_database(&root.join("missing")),
+        Err(Error::DatabaseNotFoundError(_))
+    ));
+}
+
+#[test]
+fn compile_flags() {
+    let root = fixture("flags");
+    assert_eq!(
+        find_database(&root).unwrap(),
+        root.join("compile_flags.txt")
+    );
+    let commands: Vec<_> = load_database(&root)
+        .unwrap()
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    let summary: Vec<_> = commands
+        .iter()
+        .map(|v| (v.file.clone(), v.arguments().unwrap()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                PathBuf::from("src/main.c"),
+                vec!["cc", "-Iinclude", "-DFOO=1", "-c", "src/main.c"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            ),
+            (
+                PathBuf::from("src/sub/util.cpp"),
+                vec!["c++", "-Iinclude", "-DFOO=1", "-c", "src/sub/util.cpp"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            ),
+        ]
+    );
+    assert!(commands
+        .iter()
+        .all(|v| v.directory == root.canonicalize().unwrap()));
+}
+
+#[test]
+fn merge() {
+    let a = commands(
+        r#"[
+            {"directory": "/src", "command": "cc -c a.c", "file": "a.c"},
+            {"directory": "/src", "command": "cc -c b.c", "file": "b.c"}
+        ]"#,
+    );
+    let b = commands(
+        r#"[
+            {"directory": "/src", "command": "cc -c a.c", "file": "a.c"},
+            {"directory": "/src", "command": "cc -DB -c b.c", "file": "b.c"},
+            {"directory": "/src", "command": "cc -c c.c", "file": "c.c"}
+        ]"#,
+    );
+    let c = commands(r#"[{"directory": "/src", "command": "cc -DC -c b.c", "file": "b.c"}]"#);
+    let (merged, conflicts) = merge_databases(vec![a, b, c]);
+    let files: Vec<_> = merged.iter().map(|v| v.file.clone()).collect();
+    assert_eq!(
+        files,
+        ["a.c", "b.c", "b.c", "c.c", "b.c"]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        conflicts,
+        vec![Conflict {
+            file: PathBuf::from("/src/b.c"),
+            databases: vec![0, 1, 2],
+        }]
+    );
+}
+
+#[test]
+fn discover() {
+    assert_eq!(
+        discover_databases(&fixture("")).unwrap(),
+        vec![fixture("tree/build/compile_commands.json")]
+    );
+}
@@ -0,0 +1,91 @@
+use dump_dependency::{dump_dependencies, CompileCommand, DumpOptions};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn temporary_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "dump-dependency-dependency-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Writes a compiler into `directory` that records its arguments into `argv` and reports `sys/s.h`
+/// under the system directory `sys` even for `-MM`.
+fn fake_compiler(directory: &Path) -> PathBuf {
+    let path = directory.join("fake-gcc");
+    fs::write(
+        &path,
+        r#"#!/bin/sh
+case "$*" in
+*" -v "*)
+    echo '#include <...> search starts here:' >&2
+    echo " $PWD/sys" >&2
+    echo 'End of search list.' >&2
+    ;;
+*)
+    echo "$@" >> argv
+    echo 'a.o: a.c a.h sys/s.h'
+    ;;
+esac
+"#,
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn run(name: &str, user_headers_only: bool) -> (Vec<String>, Vec<PathBuf>, PathBuf) {
+    let directory = temporary_directory(name);
+    let compiler = fake_compiler(&directory);
+    fs::create_dir_all(directory.join("sys")).unwrap();
+    for file in ["a.c", "a.h", "sys/s.h"] {
+        fs::write(directory.join(file), "").unwrap();
+    }
+    let command = CompileCommand {
+        directory: directory.clone(),
+        command: Some(format!("{} -c a.c -o a.o", compiler.display())),
+        arguments: None,
+        file: PathBuf::from("a.c"),
+        output: None,
+    };
+    let options = DumpOptions {
+        user_headers_only,
+        ..Default::default()
+    };
+    let unit = dump_dependencies(&[command], &options).remove(0);
+    assert!(unit.error.is_none(), "{:?}", unit.error);
+    let argv = fs::read_to_string(directory.join("argv")).unwrap();
+    let argv = argv.split_whitespace().map(String::from).collect();
+    let canonical = directory.canonicalize().unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    (argv, unit.dependencies, canonical)
+}
+
+#[test]
+fn user_headers_only_passes_mm() {
+    let (argv, _, _) = run("mm", true);
+    assert!(argv.iter().any(|v| v == "-MM"), "{:?}", argv);
+    assert!(!argv.iter().any(|v| v == "-M"), "{:?}", argv);
+}
+
+#[test]
+fn all_headers_pass_m() {
+    let (argv, dependencies, directory) = run("m", false);
+    assert!(argv.iter().any(|v| v == "-M"), "{:?}", argv);
+    assert!(!argv.iter().any(|v| v == "-MM"), "{:?}", argv);
+    assert!(dependencies.contains(&directory.join("sys/s.h")));
+}
+
+#[test]
+fn system_headers_reported_by_mm_are_filtered() {
+    let (_, dependencies, directory) = run("filter", true);
+    assert_eq!(
+        dependencies,
+        vec![directory.join("a.c"), directory.join("a.h")]
+    );
+}
@@ -0,0 +1,159 @@
+use dump_dependency::{parse_dependency, parse_depfile, Depfile, Error};
+use std::fs;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> Depfile {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/depfile")
+        .join(name);
+    let content = fs::read_to_string(&path).unwrap();
+    parse_depfile(&content).unwrap()
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+fn project_headers() -> Vec<PathBuf> {
+    paths(&["main.c", "plain.h", "dir with space/a b.h", "we$ird#name.h"])
+}
+
+#[test]
+fn gcc_m() {
+    let depfile = fixture("gcc-M.d");
+    assert_eq!(depfile.rules.len(), 1);
+    assert_eq!(depfile.rules[0].targets, paths(&["main.o"]));
+    assert_eq!(
+        depfile.rules[0].prerequisites,
+        paths(&[
+            "main.c",
+            "/usr/include/stdc-predef.h",
+            "plain.h",
+            "dir with space/a b.h",
+            "we$ird#name.h",
+            "/usr/lib/gcc/x86_64-linux-gnu/12/include/stddef.h",
+        ])
+    );
+}
+
+#[test]
+fn gcc_m_keeps_last_line() {
+    let depfile = fixture("gcc-M-stdio.d");
+    assert_eq!(depfile.targets(), vec![Path::new("hello.o")]);
+    let prerequisites = depfile.prerequisites();
+    assert_eq!(prerequisites.len(), 26);
+    assert_eq!(prerequisites.first(), Some(&Path::new("hello.c")));
+    assert_eq!(
+        prerequisites.last(),
+        Some(&Path::new(
+            "/usr/include/x86_64-linux-gnu/bits/floatn-common.h"
+        ))
+    );
+}
+
+#[test]
+fn gcc_mp_multiple_targets() {
+    let depfile = fixture("gcc-MM-MP-multiple-targets.d");
+    assert_eq!(depfile.rules.len(), 4);
+    assert_eq!(
+        depfile.targets(),
+        vec![Path::new("main.o"), Path::new("main.d")]
+    );
+    assert_eq!(depfile.rules[0].prerequisites, project_headers());
+    assert_eq!(depfile.rules[2].targets, paths(&["dir with space/a b.h"]));
+    assert!(depfile.rules[1..]
+        .iter()
+        .all(|v| v.prerequisites.is_empty()));
+}
+
+#[test]
+fn gcc_mq() {
+    let depfile = fixture("gcc-MM-MQ.d");
+    assert_eq!(depfile.targets(), vec![Path::new("out$.o")]);
+    assert_eq!(depfile.rules[0].prerequisites, project_headers());
+}
+
+#[test]
+fn gcc_mmd_mf() {
+    let depfile = fixture("gcc-MMD-MF.d");
+    assert_eq!(depfile.targets(), vec![Path::new("main.o")]);
+    assert_eq!(depfile.rules[0].prerequisites, project_headers());
+}
+
+#[test]
+fn clang_m() {
+    let depfile = fixture("clang-M.d");
+    assert_eq!(depfile.targets(), vec![Path::new("main.o")]);
+    assert_eq!(
+        depfile.rules[0].prerequisites,
+        paths(&[
+            "main.c",
+            "/usr/include/stdc-predef.h",
+            "plain.h",
+            "dir with space/a b.h",
+            "we$ird#name.h",
+            "/usr/lib/llvm-14/lib/clang/14.0.6/include/stddef.h",
+        ])
+    );
+}
+
+#[test]
+fn clang_mp_multiple_targets() {
+    assert_eq!(
+        fixture("clang-MM-MP-multiple-targets.d"),
+        fixture("gcc-MM-MP-multiple-targets.d")
+    );
+}
+
+#[test]
+fn clang_mq() {
+    assert_eq!(fixture("clang-MM-MQ.d"), fixture("gcc-MM-MQ.d"));
+}
+
+#[test]
+fn crlf() {
+    let depfile = fixture("crlf.d");
+    assert_eq!(
+        depfile.rules[0].prerequisites,
+        paths(&["main.c", "plain.h"])
+    );
+}
+
+#[test]
+fn backslashes() {
+    let depfile = parse_depfile("a.o: x\\\\\\ y.h C:\\src\\b.h z\\\\ w.h # comment\n").unwrap();
+    assert_eq!(
+        depfile.rules[0].prerequisites,
+        paths(&["x\\ y.h", "C:\\src\\b.h", "z\\\\", "w.h"])
+    );
+}
+
+#[test]
+fn windows_drive_letter() {
+    let depfile = parse_depfile("C:\\out\\a.obj: C:\\src\\a.c\n").unwrap();
+    assert_eq!(depfile.targets(), vec![Path::new("C:\\out\\a.obj")]);
+    assert_eq!(depfile.prerequisites(), vec![Path::new("C:\\src\\a.c")]);
+}
+
+#[test]
+fn missing_colon() {
+    assert!(matches!(
+        parse_depfile("a.o: a.c\nb.c b.h\n"),
+        Err(Error::DepfileFormatError(2))
+    ));
+}
+
+#[test]
+fn parse_dependency_resolves_relative_paths() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = "lib.o: src/lib.rs \\\n Cargo.toml not-found.h\n";
+    let result = parse_dependency(BufReader::new(Cursor::new(output)), directory).unwrap();
+    assert_eq!(
+        result,
+        vec![
+            directory.join("src/lib.rs").canonicalize().unwrap(),
+            directory.join("Cargo.toml").canonicalize().unwrap(),
+        ]
+    );
+}
@@ -0,0 +1,6 @@
+#pragma once
+/* #include "commented.h"
+   #include "commented.h" */
+// #include "commented.h"
+#include \
+    "continued.h"
@@ -0,0 +1,33 @@
+#include "local.h"
+#include <lib.h>
+#include "quoted.h"
+#include "missing.h"
+
+#ifdef USE_FOO
+#include "foo.h"
+#else
+#include "bar.h"
+#endif
+
+#if defined(__GNUC__) && VERSION >= 2
+#include "gnu.h"
+#endif
+
+#if 0
+#include "never.h"
+#endif
+
+#define HEADER "macro.h"
+#include HEADER
+
+#if __has_include(<optional.h>)
+#  include <optional.h>
+#endif
+
+#if (1 + 2 * 3 == 7) && !(4 / 2 - 2) && (1 ? 0x10 : 0) == 16 && (1 << 3) == 010 && 'a' == 97
+#include "yes.h"
+#elif defined UNDEFINED
+#include "no.h"
+#endif
+
+int main(void) { return 0; }
@@ -0,0 +1 @@
+#include "inner.h"
@@ -0,0 +1,107 @@
+use dump_dependency::graph::{write_dot, GraphOptions};
+use dump_dependency::{parse_compile_commands, DependencyKind, Error, TranslationUnit};
+use std::path::{Path, PathBuf};
+
+fn unit(file: &str, dependencies: &[&str]) -> TranslationUnit {
+    let content = format!(
+        r#"[{{"directory": "/src", "command": "cc -c {}", "file": "{}"}}]"#,
+        file, file
+    );
+    let entries = parse_compile_commands(&content, Path::new("/")).unwrap();
+    let command = entries[0].as_ref().unwrap();
+    let dependencies = dependencies.iter().map(PathBuf::from).collect();
+    let mut unit = TranslationUnit::new(command, Ok(dependencies));
+    unit.system_directories = vec![PathBuf::from("/usr/include")];
+    unit
+}
+
+fn units() -> Vec<TranslationUnit> {
+    let mut failed = unit("/src/c/c.c", &[]);
+    failed.error = Some(Error::CommandFormatError);
+    vec![
+        unit(
+            "/src/a/a.c",
+            &[
+                "/src/a/a.c",
+                "/src/inc/a\"quote.h",
+                "/usr/include/stdio.h",
+                "/usr/include/string.h",
+            ],
+        ),
+        unit("/src/b/b.c", &["/src/b/b.c", "/src/inc/a\"quote.h"]),
+        failed,
+    ]
+}
+
+fn dot<F>(options: GraphOptions, filter: F) -> String
+where
+    F: Fn(&Path, DependencyKind) -> bool,
+{
+    let mut output = Vec::new();
+    write_dot(&mut output, &units(), options, filter).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn plain() {
+    assert_eq!(
+        dot(GraphOptions::default(), |_, _| true),
+        r#"digraph dependency {
+    rankdir=LR;
+    "/src/a/a.c" [label="/src/a/a.c", shape=box];
+    "/src/b/b.c" [label="/src/b/b.c", shape=box];
+    "/src/inc/a\"quote.h" [label="/src/inc/a\"quote.h", shape=ellipse];
+    "/usr/include/stdio.h" [label="/usr/include/stdio.h", shape=ellipse];
+    "/usr/include/string.h" [label="/usr/include/string.h", shape=ellipse];
+    "/src/a/a.c" -> "/src/inc/a\"quote.h";
+    "/src/a/a.c" -> "/usr/include/stdio.h";
+    "/src/a/a.c" -> "/usr/include/string.h";
+    "/src/b/b.c" -> "/src/inc/a\"quote.h";
+}
+"#
+    );
+}
+
+#[test]
+fn collapsed_clusters() {
+    let options = GraphOptions {
+        cluster_by_directory: true,
+        collapse_system_headers: true,
+    };
+    assert_eq!(
+        dot(options, |_, _| true),
+        r#"digraph dependency {
+    rankdir=LR;
+    "<system headers>" [label="<system headers>", shape=folder];
+    subgraph "cluster_1" {
+        label="/src/a";
+        "/src/a/a.c" [label="a.c", shape=box];
+    }
+    subgraph "cluster_2" {
+        label="/src/b";
+        "/src/b/b.c" [label="b.c", shape=box];
+    }
+    subgraph "cluster_3" {
+        label="/src/inc";
+        "/src/inc/a\"quote.h" [label="a\"quote.h", shape=ellipse];
+    }
+    "/src/a/a.c" -> "/src/inc/a\"quote.h";
+    "/src/a/a.c" -> "<system headers>";
+    "/src/b/b.c" -> "/src/inc/a\"quote.h";
+}
+"#
+    );
+}
+
+#[test]
+fn filtered() {
+    let options = GraphOptions {
+        collapse_system_headers: true,
+        ..GraphOptions::default()
+    };
+    let dot = dot(options, |_, kind| kind != DependencyKind::System);
+    assert!(!dot.contains("<system headers>"));
+    assert!(!dot.contains("stdio.h"));
+    assert!(dot.contains(r#""/src/b/b.c" -> "/src/inc/a\"quote.h";"#));
+    assert!(!dot.contains("c.c"));
+}
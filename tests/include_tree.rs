@@ -0,0 +1,220 @@
+use dump_dependency::{
+    dependents, describe_dependents, include_chains, parse_include_trace, CompileCommand,
+    DependentField, Include, TranslationUnit,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> Vec<Include> {
+    let content = fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/include_tree")
+            .join(name),
+    )
+    .unwrap();
+    parse_include_trace(content.as_slice(), Path::new("/build")).unwrap()
+}
+
+fn include(path: &str, includes: Vec<Include>) -> Include {
+    Include {
+        path: Path::new("/build").join(path),
+        includes,
+    }
+}
+
+fn paths(includes: &[Include]) -> Vec<PathBuf> {
+    includes
+        .iter()
+        .flat_map(Include::iter)
+        .map(|v| v.path.clone())
+        .collect()
+}
+
+const STDDEF: &str = "/usr/lib/gcc/x86_64-linux-gnu/12/include/stddef.h";
+
+// Captured with `gcc -Iinclude -include pch.h -H -fsyntax-only main.c`, where main.c includes
+// a.h, which includes b.h, noguard.h twice and stddef.h, and has a `#warning`
+#[test]
+fn gcc_h() {
+    assert_eq!(
+        fixture("gcc-H.txt"),
+        vec![
+            include("pch.h.gch", vec![]),
+            include("include/a.h", vec![include("include/b.h", vec![])]),
+            include("include/noguard.h", vec![]),
+            include("include/noguard.h", vec![]),
+            include(STDDEF, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn invalid_precompiled_header() {
+    let includes = fixture("gcc-H-invalid-pch.txt");
+    assert_eq!(includes[0], include("pch.h.gch", vec![]));
+    assert_eq!(includes.len(), 5);
+}
+
+#[test]
+fn skips_diagnostics_and_include_guard_hints() {
+    for name in ["gcc-H.txt", "gcc-H-invalid-pch.txt"] {
+        let paths = paths(&fixture(name));
+        assert_eq!(paths.len(), 6, "{}", name);
+        // `./pch.h` and `include/a.h` are listed again after "Multiple include guards may be
+        // useful for:"
+        assert!(!paths.contains(&PathBuf::from("/build/pch.h")), "{}", name);
+        assert_eq!(
+            paths.iter().filter(|v| v.ends_with("include/a.h")).count(),
+            1,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn depth_from_dots() {
+    let trace = ". a.h\n.. b.h\n... c.h\n.. d.h\n. e.h\n";
+    assert_eq!(
+        parse_include_trace(trace.as_bytes(), Path::new("/build")).unwrap(),
+        vec![
+            include(
+                "a.h",
+                vec![
+                    include("b.h", vec![include("c.h", vec![])]),
+                    include("d.h", vec![]),
+                ]
+            ),
+            include("e.h", vec![]),
+        ]
+    );
+}
+
+#[test]
+fn keeps_absolute_paths() {
+    let trace = format!(". {}\n", STDDEF);
+    let includes = parse_include_trace(trace.as_bytes(), Path::new("/build")).unwrap();
+    assert!(includes[0].path.ends_with("stddef.h"));
+    assert!(!includes[0].path.starts_with("/build"));
+}
+
+/// a.h -> b.h -> c.h, c.h, d.h -> b.h -> c.h
+fn diamond() -> Vec<Include> {
+    let b = include("b.h", vec![include("c.h", vec![])]);
+    vec![
+        include("a.h", vec![b.clone()]),
+        include("c.h", vec![]),
+        include("d.h", vec![b]),
+    ]
+}
+
+fn chain(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(|v| Path::new("/build").join(v)).collect()
+}
+
+fn chains(includes: &[Include], path: &str) -> Vec<Vec<PathBuf>> {
+    include_chains(includes, &Path::new("/build").join(path))
+        .into_iter()
+        .map(|v| v.into_iter().map(Path::to_path_buf).collect())
+        .collect()
+}
+
+#[test]
+fn chains_shortest_first() {
+    assert_eq!(
+        chains(&diamond(), "c.h"),
+        vec![
+            chain(&["c.h"]),
+            chain(&["a.h", "b.h", "c.h"]),
+            chain(&["d.h", "b.h", "c.h"]),
+        ]
+    );
+}
+
+#[test]
+fn chains_through_several_paths() {
+    assert_eq!(
+        chains(&diamond(), "b.h"),
+        vec![chain(&["a.h", "b.h"]), chain(&["d.h", "b.h"])]
+    );
+    assert_eq!(chains(&diamond(), "a.h"), vec![chain(&["a.h"])]);
+}
+
+#[test]
+fn no_chain() {
+    assert!(chains(&diamond(), "e.h").is_empty());
+    assert!(chains(&[], "a.h").is_empty());
+}
+
+fn compile_command(command: &str, output: Option<&str>) -> CompileCommand {
+    CompileCommand {
+        directory: PathBuf::from("/build"),
+        command: Some(String::from(command)),
+        arguments: None,
+        file: PathBuf::from(command.rsplit(' ').next().unwrap()),
+        output: output.map(PathBuf::from),
+    }
+}
+
+/// Two variants of `a.c` and `b.c` that depend on `src/lib.rs` of this crate, and `c.c` that does
+/// not.
+fn rdeps_database() -> (Vec<CompileCommand>, Vec<TranslationUnit>) {
+    let header = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/lib.rs")
+        .canonicalize()
+        .unwrap();
+    let commands = vec![
+        compile_command("cc -DA -c a.c", Some("a-A.o")),
+        compile_command("cc -DB -c a.c", Some("a-B.o")),
+        compile_command("cc -c b.c", None),
+        compile_command("cc -c c.c", Some("c.o")),
+    ];
+    let units = commands
+        .iter()
+        .map(|command| {
+            let dependencies = if command.file == Path::new("c.c") {
+                vec![PathBuf::from("/build/c.c")]
+            } else {
+                vec![Path::new("/build").join(&command.file), header.clone()]
+            };
+            TranslationUnit::new(command, Ok(dependencies))
+        })
+        .collect();
+    (commands, units)
+}
+
+#[test]
+fn dependents_of_relative_path() {
+    // Tests run in the directory of the package
+    let (commands, units) = rdeps_database();
+    let matched = dependents(&[PathBuf::from("src/lib.rs")], &commands, &units);
+    let lines: Vec<_> = matched.iter().map(|(v, _)| v.command.as_deref()).collect();
+    assert_eq!(
+        lines,
+        vec![
+            Some("cc -DA -c a.c"),
+            Some("cc -DB -c a.c"),
+            Some("cc -c b.c")
+        ]
+    );
+    assert!(dependents(&[PathBuf::from("src/none.h")], &commands, &units).is_empty());
+}
+
+#[test]
+fn describe_each_field() {
+    let (commands, units) = rdeps_database();
+    let matched = dependents(&[PathBuf::from("src/lib.rs")], &commands, &units);
+    assert_eq!(
+        describe_dependents(&matched, DependentField::File),
+        vec!["a.c", "b.c"]
+    );
+    // b.c has no output
+    assert_eq!(
+        describe_dependents(&matched, DependentField::Output),
+        vec!["a-A.o", "a-B.o"]
+    );
+    assert_eq!(
+        describe_dependents(&matched, DependentField::Command),
+        vec!["cc -DA -c a.c", "cc -DB -c a.c", "cc -c b.c"]
+    );
+}
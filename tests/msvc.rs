@@ -0,0 +1,113 @@
+use dump_dependency::{parse_show_includes, Include, Msvc};
+use std::fs;
+use std::path::Path;
+
+fn parse(name: &str, prefix: &str) -> (Vec<Include>, Vec<String>) {
+    let content = fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/msvc")
+            .join(name),
+    )
+    .unwrap();
+    parse_show_includes(content.as_slice(), Path::new("/build"), prefix).unwrap()
+}
+
+fn fixture(name: &str, prefix: &str) -> Vec<Include> {
+    parse(name, prefix).0
+}
+
+fn include(path: &str, includes: Vec<Include>) -> Include {
+    Include {
+        path: Path::new("/build").join(path),
+        includes,
+    }
+}
+
+const UCRT: &str = r"C:\Program Files (x86)\Windows Kits\10\Include\10.0.22621.0\ucrt\";
+
+fn ucrt(name: &str) -> String {
+    format!("{}{}", UCRT, name)
+}
+
+// In the format of `cl /nologo /showIncludes /Zs main.cpp`, with CRLF line endings
+#[test]
+fn cl() {
+    assert_eq!(
+        fixture("cl-showIncludes.txt", Msvc::DEFAULT_INCLUDE_PREFIX),
+        vec![
+            include(
+                r"C:\src\app\include\a.h",
+                vec![include(r"C:\src\app\include\b.h", vec![])]
+            ),
+            include(
+                &ucrt("stdio.h"),
+                vec![include(
+                    &ucrt("corecrt_wstdio.h"),
+                    vec![include(&ucrt("corecrt_stdio_config.h"), vec![])]
+                )]
+            ),
+        ]
+    );
+}
+
+// clang-cl does not echo the source name and keeps paths as written
+#[test]
+fn clang_cl() {
+    assert_eq!(
+        fixture("clang-cl-showIncludes.txt", Msvc::DEFAULT_INCLUDE_PREFIX),
+        vec![
+            include(r".\include\a.h", vec![include(r".\include\b.h", vec![])]),
+            include(
+                &ucrt("stdio.h"),
+                vec![include(&ucrt("corecrt_wstdio.h"), vec![])]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn localized_prefix() {
+    let expected = vec![include(
+        r"C:\src\app\include\a.h",
+        vec![include(r"C:\src\app\include\b.h", vec![])],
+    )];
+    assert_eq!(
+        fixture("cl-showIncludes-de.txt", "Hinweis: Einlesen der Datei:"),
+        expected
+    );
+    // Notes in another language are not includes
+    assert!(fixture("cl-showIncludes-de.txt", Msvc::DEFAULT_INCLUDE_PREFIX).is_empty());
+}
+
+#[test]
+fn returns_other_lines() {
+    let (includes, messages) = parse("cl-showIncludes.txt", Msvc::DEFAULT_INCLUDE_PREFIX);
+    let paths: Vec<_> = includes.iter().flat_map(Include::iter).collect();
+    assert_eq!(paths.len(), 5);
+    assert!(!paths.iter().any(|v| v.path.ends_with("main.cpp")));
+    assert_eq!(
+        messages,
+        vec![
+            "main.cpp",
+            "main.cpp(6): warning C4996: 'strcpy': This function or variable may be unsafe. Consider using strcpy_s instead.",
+        ]
+    );
+    assert!(
+        parse("clang-cl-showIncludes.txt", Msvc::DEFAULT_INCLUDE_PREFIX)
+            .1
+            .is_empty()
+    );
+}
+
+#[test]
+fn keeps_lines_regardless_of_spaces() {
+    let output = "my main.cpp\r\nLNK1104\r\n\r\nNote: including file: a.h\r\n";
+    let (includes, messages) = parse_show_includes(
+        output.as_bytes(),
+        Path::new("/build"),
+        Msvc::DEFAULT_INCLUDE_PREFIX,
+    )
+    .unwrap();
+    assert_eq!(includes, vec![include("a.h", vec![])]);
+    assert_eq!(messages, vec!["my main.cpp", "LNK1104"]);
+}
@@ -0,0 +1,159 @@
+use dump_dependency::{
+    ninja_dependencies, parse_ninja_deps, CompileCommand, Error, NinjaDepsRecord,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes `.ninja_deps` the way Ninja does.
+struct Log {
+    version: u32,
+    content: Vec<u8>,
+    paths: Vec<String>,
+}
+
+impl Log {
+    fn new(version: u32) -> Self {
+        let mut content = b"# ninjadeps\n".to_vec();
+        content.extend(version.to_le_bytes());
+        Log {
+            version,
+            content,
+            paths: Vec::new(),
+        }
+    }
+
+    fn id(&mut self, path: &str) -> u32 {
+        if let Some(id) = self.paths.iter().position(|v| v == path) {
+            return id as u32;
+        }
+        let id = self.paths.len() as u32;
+        let mut record = path.as_bytes().to_vec();
+        while !record.len().is_multiple_of(4) {
+            record.push(0);
+        }
+        record.extend((!id).to_le_bytes());
+        self.content.extend((record.len() as u32).to_le_bytes());
+        self.content.extend(record);
+        self.paths.push(String::from(path));
+        id
+    }
+
+    fn deps(&mut self, output: &str, mtime: u64, inputs: &[&str]) -> &mut Self {
+        let output = self.id(output);
+        let inputs: Vec<u32> = inputs.iter().map(|v| self.id(v)).collect();
+        let mut ids = vec![output];
+        match self.version {
+            3 => ids.push(mtime as u32),
+            _ => ids.extend([mtime as u32, (mtime >> 32) as u32]),
+        }
+        ids.extend(inputs);
+        self.content
+            .extend(((ids.len() * 4) as u32 | 0x8000_0000).to_le_bytes());
+        for id in ids {
+            self.content.extend(id.to_le_bytes());
+        }
+        self
+    }
+}
+
+fn record(output: &str, mtime: u64, dependencies: &[&str]) -> NinjaDepsRecord {
+    NinjaDepsRecord {
+        output: PathBuf::from(output),
+        mtime,
+        dependencies: dependencies.iter().map(PathBuf::from).collect(),
+    }
+}
+
+#[test]
+fn version_3() {
+    let mut log = Log::new(3);
+    log.deps("a.o", 1234, &["../a.c", "../a.h"]);
+    let deps = parse_ninja_deps(&log.content).unwrap();
+    assert_eq!(deps.version, 3);
+    assert_eq!(
+        deps.records(),
+        vec![record("a.o", 1234, &["../a.c", "../a.h"])]
+    );
+}
+
+#[test]
+fn version_4_keeps_last_record() {
+    let mtime = 1_700_000_000_123_456_789;
+    let mut log = Log::new(4);
+    log.deps("b.o", 1, &["../b.c", "../old.h"])
+        .deps("a.o", mtime, &["../a.c", "../a.h"])
+        .deps("b.o", mtime, &["../b.c", "../a.h"]);
+    let deps = parse_ninja_deps(&log.content).unwrap();
+    assert_eq!(
+        deps.records(),
+        vec![
+            record("b.o", mtime, &["../b.c", "../a.h"]),
+            record("a.o", mtime, &["../a.c", "../a.h"]),
+        ]
+    );
+    assert_eq!(
+        deps.find(Path::new("./b.o")),
+        Some(&record("b.o", mtime, &["../b.c", "../a.h"]))
+    );
+}
+
+#[test]
+fn truncated_tail() {
+    let mut log = Log::new(4);
+    log.deps("a.o", 1, &["../a.c"]);
+    let length = log.content.len();
+    log.deps("b.o", 1, &["../b.c"]);
+    log.content.truncate(length + 6);
+    let deps = parse_ninja_deps(&log.content).unwrap();
+    assert_eq!(deps.records(), vec![record("a.o", 1, &["../a.c"])]);
+}
+
+#[test]
+fn malformed() {
+    assert!(matches!(
+        parse_ninja_deps(b"# ninjalog\n"),
+        Err(Error::NinjaDepsFormatError(_))
+    ));
+    assert!(matches!(
+        parse_ninja_deps(&Log::new(2).content),
+        Err(Error::NinjaDepsFormatError(_))
+    ));
+}
+
+#[test]
+fn map_outputs_to_commands() {
+    let root =
+        std::env::temp_dir().join(format!("dump-dependency-test-{}-ninja", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("build")).unwrap();
+    fs::write(root.join("a.c"), "").unwrap();
+    fs::write(root.join("a.h"), "").unwrap();
+    let root = root.canonicalize().unwrap();
+
+    let mut log = Log::new(4);
+    log.deps("obj/a.o", 1, &["../a.c", "../a.h"]);
+    fs::write(root.join("build/.ninja_deps"), &log.content).unwrap();
+
+    let command = |file: &str, command: &str, output: Option<&str>| CompileCommand {
+        directory: root.join("build"),
+        command: Some(String::from(command)),
+        arguments: None,
+        file: PathBuf::from(file),
+        output: output.map(PathBuf::from),
+    };
+    let commands = vec![
+        command("../a.c", "cc -c ../a.c -o obj/a.o", None),
+        command("../a.c", "cc -c ../a.c", Some("./obj/../obj/a.o")),
+        command("../b.c", "cc -c ../b.c -o obj/b.o", None),
+    ];
+    let expected = Some(vec![root.join("a.c"), root.join("a.h")]);
+    assert_eq!(
+        ninja_dependencies(&commands, None),
+        vec![expected.clone(), expected.clone(), None]
+    );
+    assert_eq!(
+        ninja_dependencies(&commands[..1], Some(&root.join("build/.ninja_deps"))),
+        vec![expected]
+    );
+    fs::remove_dir_all(root).unwrap();
+}
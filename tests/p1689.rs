@@ -0,0 +1,178 @@
+use dump_dependency::report::ModuleReport;
+use dump_dependency::{
+    dump_modules, parse_p1689, CompileCommand, LookupMethod, ModuleDependencies, TranslationUnit,
+    P1689,
+};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> P1689 {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/p1689")
+        .join(name);
+    let content = fs::read_to_string(&path).unwrap();
+    parse_p1689(&content).unwrap()
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|v| String::from(*v)).collect()
+}
+
+#[test]
+fn clang_scan_deps() {
+    let p1689 = fixture("clang-scan-deps.json");
+    assert_eq!(p1689.version, 1);
+    let rule = &p1689.rules[0];
+    assert_eq!(rule.primary_output, Some(PathBuf::from("impl_part.o")));
+    assert!(!rule.provides[0].is_interface);
+    assert_eq!(
+        rule.provides[0].source_path,
+        Some(PathBuf::from("/src/impl_part.cppm"))
+    );
+    assert_eq!(
+        p1689.modules(),
+        ModuleDependencies {
+            provides: names(&["M:impl_part"]),
+            requires: names(&["M:interface_part"]),
+        }
+    );
+}
+
+#[test]
+fn gcc() {
+    let p1689 = fixture("gcc.ddi");
+    let rule = &p1689.rules[0];
+    assert!(rule.provides[0].is_interface);
+    assert_eq!(rule.requires[0].lookup_method, LookupMethod::ByName);
+    assert_eq!(rule.requires[1].lookup_method, LookupMethod::IncludeAngle);
+    assert_eq!(
+        p1689.modules(),
+        ModuleDependencies {
+            provides: names(&["foo"]),
+            requires: names(&["bar", "<vector>"]),
+        }
+    );
+}
+
+#[test]
+fn malformed() {
+    assert!(parse_p1689("{\"rules\": {}}").is_err());
+}
+
+fn unit(file: &str, provides: &[&str], requires: &[&str]) -> TranslationUnit {
+    let command = CompileCommand {
+        directory: PathBuf::from("/nonexistent"),
+        command: Some(format!("c++ -c {}", file)),
+        arguments: None,
+        file: PathBuf::from(file),
+        output: None,
+    };
+    let mut unit = TranslationUnit::new(&command, Ok(Vec::new()));
+    unit.modules = Some(ModuleDependencies {
+        provides: names(provides),
+        requires: names(requires),
+    });
+    unit
+}
+
+#[test]
+fn module_report() {
+    let units = vec![
+        unit("main.cpp", &[], &["foo", "std"]),
+        unit("foo.cppm", &["foo"], &["bar"]),
+        unit("bar.cppm", &["bar"], &[]),
+        unit("test.cpp", &[], &["foo"]),
+    ];
+    let modules = ModuleReport::collect(&units);
+    let names: Vec<_> = modules.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, ["bar", "foo", "std"]);
+    assert_eq!(modules[1].provided_by, [PathBuf::from("foo.cppm")]);
+    assert_eq!(
+        modules[1].imported_by,
+        [PathBuf::from("main.cpp"), PathBuf::from("test.cpp")]
+    );
+    assert!(modules[2].provided_by.is_empty());
+}
+
+fn temporary_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "dump-dependency-p1689-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn script(path: &Path, content: &str) {
+    fs::write(path, content).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Writes `compiler` that rejects `-fdeps-format` and `clang-scan-deps` next to it that records
+/// its arguments into `argv`.
+fn fake_clang(directory: &Path, compiler: &str) -> PathBuf {
+    let compiler = directory.join(compiler);
+    script(
+        &compiler,
+        "#!/bin/sh\necho \"$0: error: unknown argument: '-fdeps-format=p1689r5'\" >&2\nexit 1\n",
+    );
+    script(
+        &directory.join("clang-scan-deps"),
+        r#"#!/bin/sh
+echo "$@" > argv
+echo '{"version": 1, "revision": 0, "rules": [{"primary-output": "m.o", "provides": [{"logical-name": "m", "is-interface": true}]}]}'
+"#,
+    );
+    compiler
+}
+
+fn dump(name: &str, compiler: &str, arguments: &[&str], output: Option<&str>) -> Vec<String> {
+    let directory = temporary_directory(name);
+    let compiler = fake_clang(&directory, compiler);
+    let mut args = vec![compiler.display().to_string()];
+    args.extend(arguments.iter().map(|v| v.to_string()));
+    let command = CompileCommand {
+        directory: directory.clone(),
+        command: None,
+        arguments: Some(args),
+        file: PathBuf::from("m.cppm"),
+        output: output.map(PathBuf::from),
+    };
+    let modules = dump_modules(&command);
+    let argv = fs::read_to_string(directory.join("argv"));
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(modules.unwrap().provides, names(&["m"]));
+    argv.unwrap().split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn clang_keeps_output() {
+    let argv = dump(
+        "clang",
+        "clang++",
+        &["-MD", "-c", "m.cppm", "-o", "m.o"],
+        None,
+    );
+    assert_eq!(argv[0], "-format=p1689");
+    assert!(
+        argv.ends_with(&names(&["-c", "m.cppm", "-o", "m.o"])),
+        "{:?}",
+        argv
+    );
+    assert!(!argv.contains(&String::from("-MD")), "{:?}", argv);
+}
+
+#[test]
+fn clang_takes_output_of_compile_command() {
+    let argv = dump("output", "clang++", &["-c", "m.cppm"], Some("m.o"));
+    assert!(argv.ends_with(&names(&["-o", "m.o"])), "{:?}", argv);
+}
+
+#[test]
+fn falls_back_to_clang_scan_deps() {
+    let argv = dump("fallback", "c++", &["-c", "m.cppm", "-o", "m.o"], None);
+    assert_eq!(argv[0], "-format=p1689");
+}
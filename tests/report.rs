@@ -0,0 +1,224 @@
+use dump_dependency::report::{
+    DiscoveryReport, ProjectReport, Report, SharedHeaderReport, TranslationUnitReport,
+    VersionedRecord, SCHEMA_VERSION,
+};
+use dump_dependency::{parse_compile_commands, DependencyKind, Error, TranslationUnit};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn unit(file: &str, dependencies: &[&str]) -> TranslationUnit {
+    let content = format!(
+        r#"[{{"directory": "/src", "command": "cc -c {}", "file": "{}"}}]"#,
+        file, file
+    );
+    let entries = parse_compile_commands(&content, Path::new("/")).unwrap();
+    let command = entries[0].as_ref().unwrap();
+    let dependencies = dependencies.iter().map(PathBuf::from).collect();
+    TranslationUnit::new(command, Ok(dependencies))
+}
+
+#[test]
+fn shared_headers() {
+    let a = vec![unit(
+        "/src/a/a.c",
+        &["/src/a/a.c", "/src/common.h", "/src/a/a.h"],
+    )];
+    let b = vec![
+        unit("/src/b/b.c", &["/src/b/b.c", "/src/common.h"]),
+        unit("/src/b/c.c", &["/src/b/c.c", "/src/a/a.h", "/src/common.h"]),
+    ];
+    let c = vec![unit("/src/a/a.c", &["/src/a/a.c"])];
+    let projects = [
+        (PathBuf::from("a"), a.as_slice()),
+        (PathBuf::from("b"), b.as_slice()),
+        (PathBuf::from("c"), c.as_slice()),
+    ];
+
+    let headers = SharedHeaderReport::collect(&projects, |_, _| true);
+    let summary: Vec<_> = headers
+        .iter()
+        .map(|v| (v.path.clone(), v.projects.clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                PathBuf::from("/src/a/a.h"),
+                vec![PathBuf::from("a"), PathBuf::from("b")]
+            ),
+            (
+                PathBuf::from("/src/common.h"),
+                vec![PathBuf::from("a"), PathBuf::from("b")]
+            ),
+        ]
+    );
+    assert_eq!(headers[0].kind, DependencyKind::Project);
+
+    let filtered = SharedHeaderReport::collect(&projects, |path, _| path.ends_with("common.h"));
+    assert_eq!(filtered.len(), 1);
+}
+
+/// Out-of-source build of `src/a.c` in `build`, depending on a project header, a system header and
+/// a header generated in `build`.
+fn out_of_source_units(name: &str) -> (PathBuf, Vec<TranslationUnit>) {
+    let root = std::env::temp_dir().join(format!(
+        "dump-dependency-report-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&root);
+    for path in ["src/a.c", "src/a.h", "build/gen.h"] {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+    let root = root.canonicalize().unwrap();
+    let content = format!(
+        r#"[{{"directory": "{}", "command": "cc -c ../src/a.c -o a.o", "file": "../src/a.c", "output": "a.o"}}]"#,
+        root.join("build").display()
+    );
+    let entries = parse_compile_commands(&content, Path::new("/")).unwrap();
+    let command = entries[0].as_ref().unwrap();
+    let dependencies = vec![
+        root.join("src/a.c"),
+        root.join("src/a.h"),
+        PathBuf::from("/usr/include/stdio.h"),
+        root.join("build/gen.h"),
+    ];
+    let mut unit = TranslationUnit::new(command, Ok(dependencies));
+    unit.system_directories = vec![PathBuf::from("/usr/include")];
+    let failed = TranslationUnit::new(command, Err(Error::CommandFormatError));
+    (root, vec![unit, failed])
+}
+
+fn report(units: &[TranslationUnit]) -> Report {
+    let mut report = Report::new(
+        units
+            .iter()
+            .map(|v| TranslationUnitReport::new(v, |_, _| true))
+            .collect(),
+    );
+    report.dependencies = Some(report.translation_units[0].dependencies.clone());
+    report
+}
+
+fn to_values(records: Vec<VersionedRecord>) -> Vec<Value> {
+    records
+        .iter()
+        .map(|v| {
+            let line = serde_json::to_string(v).unwrap();
+            assert!(!line.contains('\n'));
+            serde_json::from_str(&line).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn json_schema() {
+    let (root, units) = out_of_source_units("json");
+    let json = serde_json::to_value(report(&units)).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(json["version"], json!(SCHEMA_VERSION));
+    assert_eq!(SCHEMA_VERSION, 1);
+    let unit = &json["translation_units"][0];
+    assert_eq!(unit["file"], json!("../src/a.c"));
+    assert_eq!(unit["path"], json!(root.join("src/a.c")));
+    assert_eq!(unit["directory"], json!(root.join("build")));
+    assert_eq!(unit["output"], json!("a.o"));
+    assert_eq!(unit["error"], Value::Null);
+    assert_eq!(
+        unit["dependencies"],
+        json!([
+            {"path": root.join("src/a.c"), "kind": "project"},
+            {"path": root.join("src/a.h"), "kind": "project"},
+            {"path": "/usr/include/stdio.h", "kind": "system"},
+            {"path": root.join("build/gen.h"), "kind": "generated"},
+        ])
+    );
+    let failed = &json["translation_units"][1];
+    assert_eq!(
+        failed["error"],
+        json!("Neither `command` nor `arguments` is given")
+    );
+    assert_eq!(failed["dependencies"], json!([]));
+    assert_eq!(json["dependencies"].as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn ndjson_schema() {
+    let (root, units) = out_of_source_units("ndjson");
+    let records = to_values(report(&units).into_records());
+    fs::remove_dir_all(&root).unwrap();
+
+    let types: Vec<_> = records.iter().map(|v| v["type"].clone()).collect();
+    assert_eq!(
+        types,
+        vec![
+            json!("dependency"),
+            json!("dependency"),
+            json!("dependency"),
+            json!("dependency"),
+            json!("translation_unit"),
+            json!("translation_unit"),
+        ]
+    );
+    for record in records.iter() {
+        assert_eq!(record["version"], json!(SCHEMA_VERSION));
+        assert!(record.get("database").is_none());
+    }
+    assert_eq!(
+        records[2],
+        json!({"version": 1, "type": "dependency", "path": "/usr/include/stdio.h", "kind": "system"})
+    );
+    assert_eq!(records[4]["directory"], json!(root.join("build")));
+    assert_eq!(records[4]["dependencies"][3]["kind"], json!("generated"));
+    assert_eq!(
+        records[5]["error"],
+        json!("Neither `command` nor `arguments` is given")
+    );
+}
+
+#[test]
+fn discovery_schema() {
+    let (root, units) = out_of_source_units("discovery");
+    let database = root.join("build/compile_commands.json");
+    let mut discovery = DiscoveryReport::new(vec![ProjectReport {
+        database: database.clone(),
+        report: report(&units[..1]),
+    }]);
+    discovery.shared_headers = Some(vec![SharedHeaderReport {
+        path: root.join("src/a.h"),
+        kind: DependencyKind::Project,
+        projects: vec![
+            database.clone(),
+            PathBuf::from("other/compile_commands.json"),
+        ],
+    }]);
+    let json = serde_json::to_value(&discovery).unwrap();
+    let records = to_values(discovery.into_records());
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(json["version"], json!(SCHEMA_VERSION));
+    assert_eq!(json["projects"][0]["database"], json!(database));
+    assert_eq!(
+        json["projects"][0]["report"]["version"],
+        json!(SCHEMA_VERSION)
+    );
+    assert_eq!(
+        json["projects"][0]["report"]["translation_units"][0]["file"],
+        json!("../src/a.c")
+    );
+    assert_eq!(json["shared_headers"][0]["kind"], json!("project"));
+
+    let (last, projects) = records.split_last().unwrap();
+    for record in projects {
+        assert_eq!(record["database"], json!(database));
+        assert_eq!(record["version"], json!(SCHEMA_VERSION));
+    }
+    assert_eq!(projects.last().unwrap()["type"], json!("translation_unit"));
+    assert_eq!(last["type"], json!("shared_header"));
+    assert!(last.get("database").is_none());
+    assert_eq!(last["projects"][1], json!("other/compile_commands.json"));
+}
@@ -0,0 +1,92 @@
+use dump_dependency::{dump_dependencies, scan_dependencies, Backend, CompileCommand, DumpOptions};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Project with a fake clang-scan-deps that reports `a.c` and `c.c` but not `b.c`, and targets
+/// that are not of any command.
+fn project(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!(
+        "dump-dependency-scan-deps-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    for (file, content) in [
+        ("a.c", "int a;\n"),
+        ("a.h", "int a;\n"),
+        ("b.c", "#include \"b.h\"\n"),
+        ("b.h", "int b;\n"),
+        ("c.c", "int c;\n"),
+    ] {
+        fs::write(root.join(file), content).unwrap();
+    }
+    let executable = root.join("clang-scan-deps");
+    fs::write(
+        &executable,
+        r#"#!/bin/sh
+# -compilation-database <path> -format=make
+cp "$2" "$(dirname "$0")/database.json"
+cat <<'EOF'
+dump-dependency-target-0: a.c a.h
+dump-dependency-target-2: c.c
+dump-dependency-target-99: a.h
+other: a.h
+EOF
+"#,
+    )
+    .unwrap();
+    fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+    root.canonicalize().unwrap()
+}
+
+fn commands(root: &Path) -> Vec<CompileCommand> {
+    ["a.c", "b.c", "c.c"]
+        .iter()
+        .map(|file| CompileCommand {
+            directory: root.to_path_buf(),
+            command: Some(format!("cc -c {}", file)),
+            arguments: None,
+            file: PathBuf::from(file),
+            output: None,
+        })
+        .collect()
+}
+
+#[test]
+fn targets_map_to_commands() {
+    let root = project("targets");
+    let result = scan_dependencies(&commands(&root), &root.join("clang-scan-deps"), &[]).unwrap();
+    let database = fs::read_to_string(root.join("database.json")).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(
+        result,
+        vec![
+            Some(vec![root.join("a.c"), root.join("a.h")]),
+            None,
+            Some(vec![root.join("c.c")]),
+        ]
+    );
+    for i in 0..3 {
+        assert!(database.contains(&format!("\"dump-dependency-target-{}\"", i)));
+    }
+}
+
+#[test]
+fn missing_target_falls_back_to_compiler() {
+    let root = project("fallback");
+    let options = DumpOptions {
+        backend: Backend::ClangScanDeps(root.join("clang-scan-deps")),
+        ..DumpOptions::default()
+    };
+    let units = dump_dependencies(&commands(&root), &options);
+    fs::remove_dir_all(&root).unwrap();
+
+    // a.c does not include a.h. Only the fake scanner says so
+    assert!(units[0].dependencies.contains(&root.join("a.h")));
+    assert!(units[1].error.is_none());
+    assert!(units[1].dependencies.contains(&root.join("b.h")));
+    assert_eq!(units[2].dependencies, vec![root.join("c.c")]);
+}
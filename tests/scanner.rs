@@ -0,0 +1,148 @@
+use dump_dependency::{CompileCommand, Compiler, Include, ScanMode, Scanner};
+use std::path::{Path, PathBuf};
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/scanner")
+        .canonicalize()
+        .unwrap()
+}
+
+fn command(flags: &[&str]) -> CompileCommand {
+    let mut arguments = vec![
+        "cc",
+        "-nostdinc",
+        "-iquote",
+        "quote",
+        "-Iinclude",
+        "-I",
+        "include2",
+    ];
+    arguments.extend(["-isystem", "sys", "-include", "forced.h"]);
+    arguments.extend(flags);
+    arguments.extend(["-c", "src/main.c"]);
+    CompileCommand {
+        directory: root(),
+        command: None,
+        arguments: Some(arguments.into_iter().map(String::from).collect()),
+        file: PathBuf::from("src/main.c"),
+        output: None,
+    }
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(|v| root().join(v)).collect()
+}
+
+#[test]
+fn superset() {
+    let scanner = Scanner {
+        mode: ScanMode::Superset,
+    };
+    assert_eq!(
+        scanner.dependencies(&command(&[]), false).unwrap(),
+        paths(&[
+            "src/main.c",
+            "forced.h",
+            "src/local.h",
+            "src/continued.h",
+            "include/lib.h",
+            "include2/lib.h",
+            "sys/system.h",
+            "sys/inner.h",
+            "quote/quoted.h",
+            "include/foo.h",
+            "include/bar.h",
+            "include/gnu.h",
+            "include/never.h",
+            "include/optional.h",
+            "include/yes.h",
+            "include/no.h",
+        ])
+    );
+}
+
+#[test]
+fn evaluate() {
+    let scanner = Scanner {
+        mode: ScanMode::Evaluate,
+    };
+    assert_eq!(
+        scanner
+            .dependencies(&command(&["-DVERSION=2", "-DUSE_FOO"]), false)
+            .unwrap(),
+        paths(&[
+            "src/main.c",
+            "forced.h",
+            "src/local.h",
+            "src/continued.h",
+            "include/lib.h",
+            "include2/lib.h",
+            "sys/system.h",
+            "sys/inner.h",
+            "quote/quoted.h",
+            "include/foo.h",
+            // `__GNUC__` is unknown without the compiler
+            "include/gnu.h",
+            "include/macro.h",
+            "include/optional.h",
+            "include/yes.h",
+        ])
+    );
+}
+
+#[test]
+fn evaluate_undefined() {
+    let scanner = Scanner {
+        mode: ScanMode::Evaluate,
+    };
+    let dependencies = scanner
+        .dependencies(&command(&["-DVERSION=1", "-DUSE_FOO", "-UUSE_FOO"]), false)
+        .unwrap();
+    assert!(dependencies.contains(&root().join("include/bar.h")));
+    assert!(!dependencies.contains(&root().join("include/foo.h")));
+    assert!(!dependencies.contains(&root().join("include/gnu.h")));
+}
+
+#[test]
+fn user_headers_only() {
+    let scanner = Scanner::default();
+    let dependencies = scanner.dependencies(&command(&[]), true).unwrap();
+    assert!(dependencies.contains(&root().join("include2/lib.h")));
+    assert!(!dependencies.contains(&root().join("sys/system.h")));
+    assert!(!dependencies.contains(&root().join("sys/inner.h")));
+}
+
+#[test]
+fn include_next() {
+    let scanner = Scanner {
+        mode: ScanMode::Evaluate,
+    };
+    let includes = scanner.include_tree(&command(&[])).unwrap();
+    let lib = includes
+        .iter()
+        .find(|v| v.path == root().join("include/lib.h"))
+        .unwrap();
+    let mut inner = Include {
+        path: root().join("sys/system.h"),
+        includes: Vec::new(),
+    };
+    inner.includes.push(Include {
+        path: root().join("sys/inner.h"),
+        includes: Vec::new(),
+    });
+    assert_eq!(
+        lib.includes,
+        vec![Include {
+            path: root().join("include2/lib.h"),
+            includes: vec![inner],
+        }]
+    );
+}
+
+#[test]
+fn missing_source() {
+    let mut command = command(&[]);
+    command.file = PathBuf::from("src/missing.c");
+    assert!(Scanner::default().dependencies(&command, false).is_err());
+}
@@ -0,0 +1,202 @@
+use dump_dependency::{parse_search_list, CompileCommand, Error, SystemDirectories};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/system_header")
+            .join(name),
+    )
+    .unwrap()
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+fn command(directory: &Path, command: &str) -> CompileCommand {
+    CompileCommand {
+        directory: directory.to_path_buf(),
+        command: Some(String::from(command)),
+        arguments: None,
+        file: PathBuf::from("a.cc"),
+        output: None,
+    }
+}
+
+#[test]
+fn gcc_search_list() {
+    // Captured with `gcc -iquote /tmp -E -v -x c++ /dev/null -o /dev/null`
+    assert_eq!(
+        parse_search_list(fixture("gcc-E-v.txt").as_slice()).unwrap(),
+        paths(&[
+            "/usr/include/c++/12",
+            "/usr/include/x86_64-linux-gnu/c++/12",
+            "/usr/include/c++/12/backward",
+            "/usr/lib/gcc/x86_64-linux-gnu/12/include",
+            "/usr/local/include",
+            "/usr/include/x86_64-linux-gnu",
+            "/usr/include",
+        ])
+    );
+}
+
+#[test]
+fn clang_search_list() {
+    assert_eq!(
+        parse_search_list(fixture("clang-E-v.txt").as_slice()).unwrap(),
+        paths(&[
+            "/usr/local/include",
+            "/Library/Developer/CommandLineTools/usr/lib/clang/15.0.0/include",
+            "/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk/usr/include",
+            "/Library/Developer/CommandLineTools/usr/include",
+            "/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk/System/Library/Frameworks",
+        ])
+    );
+}
+
+#[test]
+fn nostdinc_search_list() {
+    // Captured with `gcc -nostdinc -E -v -x c /dev/null -o /dev/null`
+    assert!(
+        parse_search_list(fixture("gcc-nostdinc-E-v.txt").as_slice())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn missing_search_list() {
+    assert!(matches!(
+        parse_search_list("cc: error: no input files\n".as_bytes()),
+        Err(Error::SearchListNotFoundError)
+    ));
+}
+
+#[test]
+fn system_directory_options() {
+    let directory = Path::new("/nonexistent/build");
+    let command = command(
+        directory,
+        "cc -isystemsys1 -isystem sys2 --sysroot=root -idirafter after --sysrootX -c a.cc",
+    );
+    let result = SystemDirectories::new(Vec::new())
+        .offline()
+        .get(&command)
+        .unwrap();
+    for path in ["sys1", "sys2", "root", "after"] {
+        assert!(result.contains(&directory.join(path)), "{}", path);
+    }
+    assert!(!result.iter().any(|v| v.ends_with("X")));
+}
+
+fn temporary_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "dump-dependency-system-header-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Writes a compiler at `path` that prints the search list `case` chooses from its arguments.
+fn fake_compiler(path: &Path, case: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(
+        path,
+        format!(
+            r#"#!/bin/sh
+case "$*" in
+{}
+esac
+echo '#include <...> search starts here:' >&2
+[ -n "$dir" ] && echo " $dir" >&2
+echo 'End of search list.' >&2
+"#,
+            case
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn stdlib_changes_search_list() {
+    let directory = temporary_directory("stdlib");
+    let compiler = directory.join("fake-clang++");
+    fake_compiler(
+        &compiler,
+        "*-stdlib=libc++*) dir=/opt/libcxx/include/c++/v1 ;;\n*) dir=/opt/libstdcxx/include ;;",
+    );
+
+    let directories = SystemDirectories::new(Vec::new());
+    let libstdcxx = directories
+        .get(&command(
+            &directory,
+            &format!("{} -c a.cc", compiler.display()),
+        ))
+        .unwrap();
+    let libcxx = directories
+        .get(&command(
+            &directory,
+            &format!("{} -stdlib=libc++ -c a.cc", compiler.display()),
+        ))
+        .unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(libstdcxx, paths(&["/opt/libstdcxx/include"]));
+    assert_eq!(libcxx, paths(&["/opt/libcxx/include/c++/v1"]));
+}
+
+#[test]
+fn empty_search_list_is_kept() {
+    let directory = temporary_directory("nostdinc");
+    let compiler = directory.join("fake-gcc");
+    fake_compiler(&compiler, "*) dir= ;;");
+
+    let result = SystemDirectories::new(Vec::new())
+        .get(&command(
+            &directory,
+            &format!("{} -nostdinc -c a.cc", compiler.display()),
+        ))
+        .unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert!(result.is_empty(), "{:?}", result);
+}
+
+#[test]
+fn relative_paths_are_cached_per_directory() {
+    let root = temporary_directory("relative");
+    let (one, two) = (root.join("one"), root.join("two"));
+    fake_compiler(&one.join("toolchain/cc"), "*) dir=/opt/one ;;");
+    fake_compiler(&two.join("toolchain/cc"), "*) dir=/opt/two ;;");
+    fs::create_dir_all(one.join("sysroot")).unwrap();
+    fs::create_dir_all(two.join("sysroot")).unwrap();
+
+    let directories = SystemDirectories::new(Vec::new());
+    let relative_compiler: Vec<_> = [&one, &two]
+        .iter()
+        .map(|directory| {
+            directories
+                .get(&command(directory, "toolchain/cc -c a.cc"))
+                .unwrap()
+        })
+        .collect();
+    let relative_sysroot: Vec<_> = [&one, &two]
+        .iter()
+        .map(|directory| {
+            let compiler = directory.join("toolchain/cc");
+            let line = format!("{} --sysroot=sysroot -c a.cc", compiler.display());
+            directories.get(&command(directory, &line)).unwrap()
+        })
+        .collect();
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(relative_compiler[0], paths(&["/opt/one"]));
+    assert_eq!(relative_compiler[1], paths(&["/opt/two"]));
+    assert!(relative_sysroot[0].contains(&PathBuf::from("/opt/one")));
+    assert!(relative_sysroot[1].contains(&PathBuf::from("/opt/two")));
+}
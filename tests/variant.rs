@@ -0,0 +1,102 @@
+use dump_dependency::{
+    dump_dependencies, merge_variants, Backend, CompileCommand, DumpOptions, ScanMode,
+    TranslationUnit, VariantMode,
+};
+use std::path::{Path, PathBuf};
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/scanner")
+        .canonicalize()
+        .unwrap()
+}
+
+fn command(flags: &str, output: Option<&str>) -> CompileCommand {
+    CompileCommand {
+        directory: root(),
+        command: Some(format!(
+            "cc -nostdinc -iquote quote -Iinclude -Iinclude2 -isystem sys -DVERSION=2 {} -c src/main.c",
+            flags
+        )),
+        arguments: None,
+        file: PathBuf::from("src/main.c"),
+        output: output.map(PathBuf::from),
+    }
+}
+
+fn dump(commands: &[CompileCommand]) -> Vec<TranslationUnit> {
+    let options = DumpOptions {
+        backend: Backend::Scanner(ScanMode::Evaluate),
+        ..DumpOptions::default()
+    };
+    dump_dependencies(commands, &options)
+}
+
+fn has(unit: &TranslationUnit, path: &str) -> bool {
+    unit.dependencies.contains(&root().join(path))
+}
+
+#[test]
+fn labels_by_arguments() {
+    let units = dump(&[command("-DUSE_FOO", None), command("", None)]);
+    assert_eq!(units[0].variant.as_deref(), Some("-DUSE_FOO"));
+    assert_eq!(units[1].variant.as_deref(), Some("#2"));
+    assert!(has(&units[0], "include/foo.h") && !has(&units[0], "include/bar.h"));
+    assert!(has(&units[1], "include/bar.h") && !has(&units[1], "include/foo.h"));
+}
+
+#[test]
+fn labels_by_output() {
+    let units = dump(&[
+        command("-DUSE_FOO", Some("foo/main.o")),
+        command("", Some("bar/main.o")),
+        command("-DVERSION=3", Some("baz/main.o")),
+    ]);
+    let labels: Vec<_> = units.iter().map(|v| v.variant.as_deref()).collect();
+    assert_eq!(
+        labels,
+        [Some("foo/main.o"), Some("bar/main.o"), Some("baz/main.o")]
+    );
+}
+
+#[test]
+fn single_command() {
+    let units = dump(&[command("", None)]);
+    assert_eq!(units[0].variant, None);
+}
+
+#[test]
+fn union() {
+    let units = dump(&[command("-DUSE_FOO", Some("a.o")), command("", Some("b.o"))]);
+    let units = merge_variants(units, VariantMode::Union);
+    assert_eq!(units.len(), 1);
+    assert_eq!(units[0].variant, None);
+    assert_eq!(units[0].output, None);
+    assert!(has(&units[0], "include/foo.h") && has(&units[0], "include/bar.h"));
+    assert!(has(&units[0], "src/local.h"));
+}
+
+#[test]
+fn intersection() {
+    let units = dump(&[command("-DUSE_FOO", None), command("", None)]);
+    let units = merge_variants(units, VariantMode::Intersection);
+    assert_eq!(units.len(), 1);
+    assert!(!has(&units[0], "include/foo.h") && !has(&units[0], "include/bar.h"));
+    assert!(has(&units[0], "src/local.h"));
+}
+
+#[test]
+fn each() {
+    let units = dump(&[command("-DUSE_FOO", None), command("", None)]);
+    assert_eq!(merge_variants(units, VariantMode::Each).len(), 2);
+}
+
+#[test]
+fn directories_written_differently() {
+    let mut other = command("", None);
+    other.directory = root().join("src/..");
+    let units = dump(&[command("-DUSE_FOO", None), other]);
+    assert_eq!(units[0].variant.as_deref(), Some("-DUSE_FOO"));
+    assert_eq!(units[1].variant.as_deref(), Some("#2"));
+    assert_eq!(merge_variants(units, VariantMode::Union).len(), 1);
+}